@@ -0,0 +1,114 @@
+use crate::{expr::Expr, literal::Literal, stmt::Stmt};
+
+/// Prints an expression as a parenthesized prefix tree, e.g.
+/// `(* (- 123) (group 45.67))` for `-123 * (45.67)`.
+pub fn print(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign(name, value, _) => parenthesize("=", &[&name.lexeme, &print(value)]),
+        Expr::Binary(left, operator, right) | Expr::Logical(left, operator, right) => {
+            parenthesize(&operator.lexeme, &[&print(left), &print(right)])
+        },
+        Expr::Unary(operator, right) => parenthesize(&operator.lexeme, &[&print(right)]),
+        Expr::Literal(Literal::String(value), _) => format!("{value:?}"),
+        Expr::Literal(value, _) => Literal::stringify(value.clone()),
+        Expr::Variable(name, _) => name.lexeme.clone(),
+        Expr::Grouping(expression, _) => parenthesize("group", &[&print(expression)]),
+        Expr::Stringify(expression) => parenthesize("str", &[&print(expression)]),
+        Expr::Call(callee, _, arguments) => {
+            let mut parts = vec![print(callee)];
+            parts.extend(arguments.iter().map(print));
+            parenthesize("call", &parts.iter().map(String::as_str).collect::<Vec<_>>())
+        },
+        Expr::Get(object, name) => parenthesize(".", &[&print(object), &name.lexeme]),
+        Expr::Set(object, name, value) => parenthesize(".=", &[&print(object), &name.lexeme, &print(value)]),
+        Expr::This(_, _) => String::from("this"),
+        Expr::Super(_, method, _) => parenthesize("super", &[&method.lexeme]),
+        Expr::List(_, items, _) => {
+            let items: Vec<String> = items.iter().map(print).collect();
+            parenthesize("list", &items.iter().map(String::as_str).collect::<Vec<_>>())
+        },
+        Expr::Map(_, entries, _) => {
+            let entries: Vec<String> = entries.iter()
+                .map(|(key, value)| parenthesize(":", &[&print(key), &print(value)]))
+                .collect();
+            parenthesize("map", &entries.iter().map(String::as_str).collect::<Vec<_>>())
+        },
+        Expr::Index(object, _, index) => parenthesize("[]", &[&print(object), &print(index)]),
+        Expr::IndexSet(object, _, index, value) => parenthesize("[]=", &[&print(object), &print(index), &print(value)]),
+    }
+}
+
+/// Prints a statement in the same style. The statements inside a block,
+/// loop, function or class go on their own lines, indented, after the
+/// declaration's `(doc "...")` if it has one.
+pub fn print_stmt(stmt: &Stmt) -> String {
+    print_stmt_at(stmt, 0)
+}
+
+fn print_stmt_at(stmt: &Stmt, depth: usize) -> String {
+    let doc = stmt.doc().map(|doc| parenthesize("doc", &[&format!("{doc:?}")]));
+    let children = |stmts: &[Stmt]| doc.iter().cloned().chain(stmts.iter().map(|stmt| print_stmt_at(stmt, depth + 1))).collect::<Vec<_>>();
+    match stmt {
+        Stmt::Expr(expression) => print(expression),
+        Stmt::Print(expression) => parenthesize("print", &[&print(expression)]),
+        Stmt::Var(name, initializer) => {
+            let mut parts = vec![name.lexeme.clone()];
+            parts.extend(initializer.as_ref().map(print));
+            parts.extend(doc);
+            parenthesize("var", &parts.iter().map(String::as_str).collect::<Vec<_>>())
+        },
+        Stmt::Block(statements, _) => nest("block", &children(statements), depth),
+        Stmt::If(condition, then_branch, else_branch) => {
+            let mut branches = vec![print_stmt_at(then_branch, depth + 1)];
+            if let Some(else_branch) = else_branch {
+                branches.push(print_stmt_at(else_branch, depth + 1));
+            }
+            nest(&format!("if {}", print(condition)), &branches, depth)
+        },
+        Stmt::While(condition, body, increment) => {
+            let mut parts = vec![print_stmt_at(body, depth + 1)];
+            if let Some(increment) = increment {
+                parts.push(parenthesize("increment", &[&print(increment)]));
+            }
+            nest(&format!("while {}", print(condition)), &parts, depth)
+        },
+        Stmt::Break(_) => String::from("(break)"),
+        Stmt::Continue(_) => String::from("(continue)"),
+        Stmt::Function(name, params, body) => {
+            let params: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+            nest(&format!("fun {} ({})", name.lexeme, params.join(" ")), &children(body), depth)
+        },
+        Stmt::Return(_, Some(value)) => parenthesize("return", &[&print(value)]),
+        Stmt::Return(_, None) => String::from("(return)"),
+        Stmt::Class(name, superclass, methods) => {
+            let header = match superclass {
+                Some(superclass) => format!("class {} < {}", name.lexeme, print(superclass)),
+                None => format!("class {}", name.lexeme),
+            };
+            nest(&header, &children(methods), depth)
+        },
+    }
+}
+
+/// `(header` followed by each child on its own line at `depth + 1`.
+fn nest(header: &str, children: &[String], depth: usize) -> String {
+    let indent = "  ".repeat(depth + 1);
+    let mut out = format!("({header}");
+    for child in children {
+        out.push('\n');
+        out.push_str(&indent);
+        out.push_str(child);
+    }
+    out.push(')');
+    out
+}
+
+fn parenthesize(name: &str, parts: &[&str]) -> String {
+    let mut out = format!("({name}");
+    for part in parts {
+        out.push(' ');
+        out.push_str(part);
+    }
+    out.push(')');
+    out
+}
@@ -0,0 +1,122 @@
+use crate::{token::Span, value::Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    FloorDivide,
+    Modulo,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Invoke,
+    SuperInvoke,
+    Closure,
+    CloseUpvalue,
+    Return,
+    BuildList,
+    BuildMap,
+    Stringify,
+    GetIndex,
+    SetIndex,
+    Class,
+    Inherit,
+    Method,
+}
+
+impl OpCode {
+    /// Every opcode, in discriminant order.
+    const ALL: [OpCode; 46] = [
+        OpCode::Constant, OpCode::Nil, OpCode::True, OpCode::False, OpCode::Pop,
+        OpCode::GetLocal, OpCode::SetLocal, OpCode::GetGlobal, OpCode::DefineGlobal, OpCode::SetGlobal,
+        OpCode::GetUpvalue, OpCode::SetUpvalue, OpCode::GetProperty, OpCode::SetProperty, OpCode::GetSuper,
+        OpCode::Equal, OpCode::Greater, OpCode::GreaterEqual, OpCode::Less, OpCode::LessEqual,
+        OpCode::Add, OpCode::Subtract, OpCode::Multiply, OpCode::Divide, OpCode::FloorDivide,
+        OpCode::Modulo, OpCode::Not, OpCode::Negate, OpCode::Print, OpCode::Jump, OpCode::JumpIfFalse, OpCode::Loop,
+        OpCode::Call, OpCode::Invoke, OpCode::SuperInvoke, OpCode::Closure, OpCode::CloseUpvalue,
+        OpCode::Return, OpCode::BuildList, OpCode::BuildMap, OpCode::Stringify, OpCode::GetIndex, OpCode::SetIndex, OpCode::Class,
+        OpCode::Inherit, OpCode::Method,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+}
+
+/// A compiled function body: the instruction stream, the constants it refers
+/// to and a run-length encoded table mapping instructions back to source.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    pub spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, byte: u8, span: Span) {
+        if self.spans.last().map(|(_, last)| *last) != Some(span) {
+            self.spans.push((self.code.len(), span));
+        }
+        self.code.push(byte);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
+        self.write(op as u8, span);
+    }
+
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        if let Some(index) = self.constants.iter().position(|constant| Chunk::same_constant(constant, &value)) {
+            return index;
+        }
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// The source span of the instruction at `offset`.
+    pub fn span_at(&self, offset: usize) -> Span {
+        let index = self.spans.partition_point(|(start, _)| *start <= offset);
+        self.spans.get(index.wrapping_sub(1)).map_or_else(Span::default, |(_, span)| *span)
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    /// Only strings, ints and floats are deduplicated; functions are always
+    /// distinct constants.
+    fn same_constant(left: &Value, right: &Value) -> bool {
+        match (left, right) {
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::Float(left), Value::Float(right)) => left.to_bits() == right.to_bits(),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{env, io::{self, IsTerminal}};
+
+use crate::code_runner::Backend;
+
+pub const USAGE: &str = "\
+Usage: rlox [options] [script [args...]]
+       rlox [options] <command> [arguments]
+
+Commands:
+  run <script> [args...]          Run a script; args() returns the arguments after it
+  repl                            Start the interactive prompt (the default without a script)
+  check <script>                  Parse and resolve a script without running it
+  fmt [--check] <file>...         Format files in place; --check only lists unformatted ones
+  tokens <script>                 Print the tokens of a script
+  ast <script>                    Print the syntax tree of a script
+  test <path>...                  Run .lox files and compare them with their '// expect:' comments
+  compile <script> [-o <output>]  Compile a script to a .loxc bytecode file
+
+Options:
+  --backend <tree|vm>             Run on the tree-walking interpreter (default) or the bytecode VM
+  --vm                            Same as --backend vm
+  --dump-bytecode                 Print the bytecode before running it; implies --backend vm
+  -W, --warnings-as-errors        Treat warnings as errors
+  --color <auto|always|never>     Colour diagnostics; auto colours them on a terminal unless NO_COLOR is set
+  -h, --help                      Print this help
+  -V, --version                   Print the version
+
+Exit codes: 0 on success, 1 when tests fail or files need formatting or can't be formatted,
+64 for bad usage, 65 for errors in the source, 70 for runtime errors.
+";
+
+const COMMANDS: [&str; 8] = ["run", "repl", "check", "fmt", "tokens", "ast", "test", "compile"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+pub enum Command {
+    Run { path: String, args: Vec<String> },
+    Repl,
+    Check(String),
+    Fmt { paths: Vec<String>, check: bool },
+    Tokens(String),
+    Ast(String),
+    Test(Vec<String>),
+    Compile { path: String, output: String },
+    Help,
+    Version,
+}
+
+/// Everything the command line asked for.
+pub struct Options {
+    pub backend: Backend,
+    pub dump_bytecode: bool,
+    pub warnings_as_errors: bool,
+    pub color: ColorChoice,
+    pub command: Command,
+}
+
+impl Options {
+    /// The options a child rlox process needs to behave like this one. Used
+    /// by `test`, which runs each script separately.
+    pub fn child_flags(&self) -> Vec<String> {
+        let backend = match self.backend {
+            Backend::TreeWalker => "tree",
+            Backend::Bytecode => "vm",
+        };
+        let mut flags = vec![String::from("--color=never"), format!("--backend={backend}")];
+        if self.warnings_as_errors {
+            flags.push(String::from("--warnings-as-errors"));
+        }
+        flags
+    }
+}
+
+/// Parses the arguments after the program name. Options may come before or
+/// after the command, but everything after the script of `run` is passed to
+/// the script untouched, dashes and all.
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        backend: Backend::TreeWalker,
+        dump_bytecode: false,
+        warnings_as_errors: false,
+        color: ColorChoice::Auto,
+        command: Command::Repl,
+    };
+    let mut check = false;
+    let mut output = None;
+    let mut positional: Vec<String> = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if starts_script(&positional) {
+            positional.push(arg.clone());
+            continue;
+        }
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        match flag {
+            "--backend" => {
+                options.backend = match value(flag, inline_value, &mut args)?.as_str() {
+                    "tree" => Backend::TreeWalker,
+                    "vm" => Backend::Bytecode,
+                    other => return Err(format!("unknown backend '{other}'; expected 'tree' or 'vm'")),
+                }
+            },
+            "--vm" => options.backend = Backend::Bytecode,
+            "--dump-bytecode" => {
+                options.backend = Backend::Bytecode;
+                options.dump_bytecode = true;
+            },
+            "-W" | "--warnings-as-errors" => options.warnings_as_errors = true,
+            "--color" => {
+                options.color = match value(flag, inline_value, &mut args)?.as_str() {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    other => return Err(format!("unknown colour choice '{other}'; expected 'auto', 'always' or 'never'")),
+                }
+            },
+            "--check" => check = true,
+            "-o" => output = Some(value(flag, inline_value, &mut args)?),
+            "-h" | "--help" => options.command = Command::Help,
+            "-V" | "--version" => options.command = Command::Version,
+            "--" => positional.extend(args.by_ref().cloned()),
+            _ if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option '{flag}'")),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if matches!(options.command, Command::Help | Command::Version) {
+        return Ok(options);
+    }
+    let command = positional.first().map(String::as_str);
+    if check && command != Some("fmt") {
+        return Err(String::from("'--check' only applies to 'fmt'"));
+    }
+    if output.is_some() && command != Some("compile") {
+        return Err(String::from("'-o' only applies to 'compile'"));
+    }
+
+    options.command = match positional.as_slice() {
+        [] => Command::Repl,
+        [command] if command == "repl" => Command::Repl,
+        [command, path, args @ ..] if command == "run" => Command::Run { path: path.clone(), args: args.to_vec() },
+        [command, path] if command == "check" => Command::Check(path.clone()),
+        [command, paths @ ..] if command == "fmt" && !paths.is_empty() => Command::Fmt { paths: paths.to_vec(), check },
+        [command, path] if command == "tokens" => Command::Tokens(path.clone()),
+        [command, path] if command == "ast" => Command::Ast(path.clone()),
+        [command, paths @ ..] if command == "test" && !paths.is_empty() => Command::Test(paths.to_vec()),
+        [command, path] if command == "compile" => {
+            let output = output.unwrap_or_else(|| compiled_path(path));
+            Command::Compile { path: path.clone(), output }
+        },
+        [command, ..] if COMMANDS.contains(&command.as_str()) => return Err(format!("wrong arguments for '{command}'")),
+        [path, args @ ..] => Command::Run { path: path.clone(), args: args.to_vec() },
+    };
+    Ok(options)
+}
+
+/// Whether the positional arguments so far include the script to run, so
+/// the rest belong to it: `run <script>` or a bare `<script>`.
+fn starts_script(positional: &[String]) -> bool {
+    match positional.first() {
+        Some(first) if first == "run" => positional.len() >= 2,
+        Some(first) => !COMMANDS.contains(&first.as_str()),
+        None => false,
+    }
+}
+
+/// The value of an option, given as `--flag=value` or `--flag value`.
+fn value<'a>(flag: &str, inline_value: Option<&str>, args: &mut impl Iterator<Item = &'a String>) -> Result<String, String> {
+    match inline_value {
+        Some(value) => Ok(value.to_string()),
+        None => args.next().cloned().ok_or(format!("'{flag}' needs a value")),
+    }
+}
+
+/// The script path with a `.loxc` extension.
+fn compiled_path(path: &str) -> String {
+    std::path::Path::new(path).with_extension("loxc").to_string_lossy().into_owned()
+}
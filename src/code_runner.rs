@@ -1,68 +1,455 @@
-use crate::{parser::Parser, scanner::Scanner, interpreter::Interpreter};
+use crate::{parser::Parser, scanner::Scanner, interpreter::Interpreter, resolver::Resolver, compiler::Compiler, vm::Vm, line_editor::{Input, LineEditor}, ast_printer, disassembler, formatter::{self, FormatError}, loxc, expr::Expr, literal::Literal, stmt::Stmt, token::Token, token_type::TokenType, value::{Function, Value}, error_handler::{LoxError, error}};
 use std::{
     fs,
-    io::{self, Write},
     process,
+    rc::Rc,
+    time::Instant,
 };
 
 enum Mode{
-    File,
-    Repl
+    File(String),
+    Repl,
+    /// A file run into the REPL session with `:load`: parsed like a file,
+    /// but errors don't end the session.
+    Load(String),
 }
 
+/// Which engine executes a program once it has been parsed and resolved.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backend {
+    TreeWalker,
+    Bytecode,
+}
+
+/// What `format_file` did with one file.
+#[derive(Clone, Copy, PartialEq)]
+pub enum FormatOutcome {
+    /// The file was already in the standard layout.
+    Formatted,
+    /// The file needed formatting: it was rewritten, or listed under `--check`.
+    Reformatted,
+    /// The source has scan or parse errors.
+    Invalid,
+    /// The file couldn't be read or written, or the formatter gave up on it.
+    Failed,
+}
+
+/// Runs source text on the selected backend. The interpreter and VM live as
+/// long as the runner, so globals defined on one REPL line are still there on
+/// the next.
 pub struct CodeRunner {
-    mode: Option<Mode>
+    mode: Option<Mode>,
+    backend: Backend,
+    dump_bytecode: bool,
+    warnings_as_errors: bool,
+    color: bool,
+    interpreter: Interpreter,
+    vm: Vm,
 }
 
 impl CodeRunner {
     pub fn new() -> Self {
-        CodeRunner { mode: None }
+        CodeRunner { mode: None, backend: Backend::TreeWalker, dump_bytecode: false, warnings_as_errors: false, color: false, interpreter: Interpreter::new(), vm: Vm::new() }
     }
 
     fn set_mode(&mut self, mode: Mode){
         self.mode = Some(mode);
     }
 
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// Prints a disassembly of every compiled chunk before running it. Only
+    /// has an effect with the bytecode backend.
+    pub fn set_dump_bytecode(&mut self, dump_bytecode: bool) {
+        self.dump_bytecode = dump_bytecode;
+    }
+
+    /// Makes warnings stop the program the way errors do.
+    pub fn set_warnings_as_errors(&mut self, warnings_as_errors: bool) {
+        self.warnings_as_errors = warnings_as_errors;
+    }
+
+    /// Whether diagnostics use ANSI colours.
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
     pub fn run_file(&mut self, path: String) {
-        self.set_mode(Mode::File);
-        let content = fs::read_to_string(path).map_err(|err| {
+        self.set_mode(Mode::File(path.clone()));
+        if loxc::is_loxc(&path) {
+            return self.run_compiled(&path);
+        }
+        let source = read_source(&path);
+        self.run(source);
+    }
+
+    /// Parses and resolves the script at `path` without running it, reporting
+    /// any errors and warnings.
+    pub fn check_file(&mut self, path: String) {
+        let source = read_source(&path);
+        self.set_mode(Mode::File(path));
+        self.analyze(&source);
+    }
+
+    pub fn print_file_tokens(&mut self, path: String) {
+        let source = read_source(&path);
+        self.set_mode(Mode::File(path));
+        self.print_tokens(&source);
+    }
+
+    /// Prints the syntax tree of every statement in the script at `path`.
+    pub fn print_file_ast(&mut self, path: String) {
+        let source = read_source(&path);
+        self.set_mode(Mode::File(path));
+        let Some(tokens) = self.scan(&source) else { return };
+        match Parser::new(tokens).parse() {
+            Ok(statements) => {
+                for statement in &statements {
+                    println!("{}", ast_printer::print_stmt(statement));
+                }
+            },
+            Err(errors) => self.handle_error(&errors, &source),
+        }
+    }
+
+    /// Rewrites the file at `path` in the standard layout. With `check` the
+    /// file is left alone and its path printed if it isn't formatted yet.
+    /// Problems are reported rather than ending the process, so the caller
+    /// can go on to the next file.
+    pub fn format_file(&mut self, path: String, check: bool) -> FormatOutcome {
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("Error reading {path}: {err}");
+                return FormatOutcome::Failed;
+            },
+        };
+        self.set_mode(Mode::File(path.clone()));
+        let formatted = match formatter::format(&source) {
+            Ok(formatted) => formatted,
+            Err(FormatError::Invalid(errors)) => {
+                self.report(&errors, &source);
+                return FormatOutcome::Invalid;
+            },
+            Err(FormatError::Changed) => {
+                eprintln!("Error formatting {path}: the result would not be the same program, so the file was left unchanged.");
+                return FormatOutcome::Failed;
+            },
+        };
+        if formatted == source {
+            return FormatOutcome::Formatted;
+        }
+        if check {
+            println!("{path}");
+        } else if let Err(err) = fs::write(&path, formatted) {
+            eprintln!("Error writing {path}: {err}");
+            return FormatOutcome::Failed;
+        }
+        FormatOutcome::Reformatted
+    }
+
+    /// Compiles the script at `path` to bytecode and writes it to `output`
+    /// in the `.loxc` format.
+    pub fn compile_file(&mut self, path: String, output: String) {
+        let source = read_source(&path);
+        self.set_mode(Mode::File(path));
+
+        let Some(statements) = self.analyze(&source) else { return };
+        match Compiler::new().compile(&statements) {
+            Ok(function) => {
+                if let Err(err) = fs::write(&output, loxc::serialize(&function)) {
+                    eprintln!("Error writing file: {err}");
+                    process::exit(1)
+                }
+            },
+            Err(errors) => self.handle_error(&errors, &source),
+        }
+    }
+
+    /// Runs a `.loxc` file. The source text is not available, so diagnostics
+    /// show locations without a snippet.
+    fn run_compiled(&mut self, path: &str) {
+        let bytes = fs::read(path).unwrap_or_else(|err| {
             eprintln!("Error reading file: {err}");
             process::exit(1)
         });
-        self.run(content.unwrap());
+        let function = loxc::deserialize(&bytes).unwrap_or_else(|message| {
+            eprintln!("Error loading {path}: {message}");
+            process::exit(65)
+        });
+        if let Err(runtime_error) = self.execute(Rc::new(function)) {
+            self.handle_runtime_error(&runtime_error, "");
+        }
     }
 
+    /// Reads and runs one statement at a time until end of input (Ctrl-D).
+    /// Input that ends inside an open bracket, string or comment continues
+    /// on the next line, and a lone expression has its value printed. A line
+    /// starting with `:` is a command, see `run_command`.
     pub fn run_prompt(&mut self) {
         self.set_mode(Mode::Repl);
+        let mut editor = match LineEditor::new() {
+            Ok(editor) => editor,
+            Err(err) => {
+                eprintln!("Error starting the line editor: {err}");
+                process::exit(1)
+            },
+        };
+        let mut source = String::new();
         loop {
-            print!("> ");
-            io::stdout().flush().unwrap();
-            let mut line = String::new();
-            io::stdin().read_line(&mut line).expect("Invalid input");
-            if line.trim().is_empty() {
-                break;
-            };
-            self.run(line);
+            let prompt = if source.is_empty() { "> " } else { "... " };
+            match editor.read_line(prompt, self.global_names()) {
+                Input::Line(line) if source.is_empty() && line.trim_start().starts_with(':') => {
+                    self.run_command(line.trim());
+                    continue;
+                },
+                Input::Line(line) => {
+                    source.push_str(&line);
+                    source.push('\n');
+                },
+                Input::Interrupted => {
+                    source.clear();
+                    continue;
+                },
+                Input::Eof => {
+                    if !source.trim().is_empty() {
+                        self.run(source);
+                    }
+                    break;
+                },
+            }
+            if source.trim().is_empty() {
+                source.clear();
+                continue;
+            }
+            if Scanner::is_incomplete(&source) {
+                continue;
+            }
+            self.run(std::mem::take(&mut source));
+        }
+        editor.save_history();
+    }
+
+    /// Runs a REPL command such as `:env` or `:load script.lox`.
+    fn run_command(&mut self, line: &str) {
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+        match (command, argument) {
+            (":env", _) => self.print_env(),
+            (":ast", expression) if !expression.is_empty() => self.print_ast(expression),
+            (":tokens", source) if !source.is_empty() => self.print_tokens(source),
+            (":load", path) if !path.is_empty() => self.load(path),
+            (":reset", _) => {
+                self.interpreter = Interpreter::new();
+                self.vm = Vm::new();
+            },
+            (":time", source) if !source.is_empty() => {
+                let start = Instant::now();
+                self.run(source.to_string());
+                println!("Took {:?}.", start.elapsed());
+            },
+            (":help", _) => {
+                println!(":env             list the global variables");
+                println!(":ast <expr>      print the syntax tree of an expression");
+                println!(":tokens <src>    print the tokens the scanner produces");
+                println!(":load <file>     run a file in this session");
+                println!(":reset           forget every global variable");
+                println!(":time <stmt>     run a statement and print how long it took");
+            },
+            (":ast" | ":tokens" | ":load" | ":time", _) => eprintln!("Usage: {command} <argument>. Type :help for details."),
+            _ => eprintln!("Unknown command '{command}'. Type :help for a list of commands."),
+        }
+    }
+
+    /// Prints the globals defined so far, leaving out the native functions.
+    fn print_env(&self) {
+        let mut bindings: Vec<(String, String)> = match self.backend {
+            Backend::TreeWalker => self.interpreter.globals.borrow().bindings().into_iter()
+                .filter(|(_, value)| !matches!(value, Literal::Native(_)))
+                .map(|(name, value)| (name, Literal::stringify(value)))
+                .collect(),
+            Backend::Bytecode => self.vm.globals().into_iter()
+                .filter(|(_, value)| !matches!(value, Value::Native(_)))
+                .map(|(name, value)| (name, value.stringify()))
+                .collect(),
+        };
+        bindings.sort();
+        for (name, value) in bindings {
+            println!("{name} = {value}");
+        }
+    }
+
+    fn print_ast(&mut self, source: &str) {
+        let Some(tokens) = self.scan(source) else { return };
+        // Allow a trailing ';' after the expression.
+        let mut expression_tokens = tokens.clone();
+        if let [.., semicolon, _] = expression_tokens.as_slice() {
+            if semicolon.token_type == TokenType::Semicolon {
+                expression_tokens.remove(expression_tokens.len() - 2);
+            }
+        }
+        if let Some(expression) = Parser::new(expression_tokens).parse_expression() {
+            return println!("{}", ast_printer::print(&expression));
+        }
+        match Parser::new(tokens).parse() {
+            Ok(_) => eprintln!(":ast takes an expression, not a statement."),
+            Err(errors) => self.handle_error(&errors, source),
+        }
+    }
+
+    fn print_tokens(&mut self, source: &str) {
+        let Some(tokens) = self.scan(source) else { return };
+        for token in tokens {
+            let position = format!("{}:{}", token.span.line, token.span.column);
+            println!("{position:<6} {:<14} {}", format!("{:?}", token.token_type), token.lexeme);
+        }
+    }
+
+    fn load(&mut self, path: &str) {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => return eprintln!("Error reading file: {err}"),
+        };
+        self.set_mode(Mode::Load(path.to_string()));
+        self.run(source);
+        self.set_mode(Mode::Repl);
+    }
+
+    fn global_names(&self) -> Vec<String> {
+        match self.backend {
+            Backend::TreeWalker => self.interpreter.globals.borrow().names(),
+            Backend::Bytecode => self.vm.global_names(),
         }
     }
 
     fn run(&mut self, source: String) {
-        let mut scanner = Scanner::new(source);
-        let tokens = scanner.scan_tokens();
-        let mut parser = Parser::new(tokens.to_vec());
-        let statements = parser.parse();
-        if !parser.get_had_err() {
-            let mut interpreter = Interpreter::new();
-            interpreter.interpret(&statements);
+        let Some(statements) = self.analyze(&source) else { return };
+        if let (Some(Mode::Repl), [Stmt::Expr(expression)]) = (&self.mode, statements.as_slice()) {
+            return self.echo(expression, &source);
+        }
+
+        let result = match self.backend {
+            Backend::TreeWalker => self.interpreter.interpret(&statements),
+            Backend::Bytecode => match Compiler::new().compile(&statements) {
+                Ok(function) => self.execute(function).map(|_| ()),
+                Err(errors) => return self.handle_error(&errors, &source),
+            },
+        };
+        if let Err(runtime_error) = result {
+            self.handle_runtime_error(&runtime_error, &source);
+        }
+    }
+
+    /// Evaluates an expression typed at the prompt and prints its value,
+    /// except for nil, the way Python's REPL leaves out `None`.
+    fn echo(&mut self, expression: &Expr, source: &str) {
+        let result = match self.backend {
+            Backend::TreeWalker => self.interpreter.evaluate(expression)
+                .map(|value| (value != Literal::Null).then(|| Literal::stringify(value))),
+            Backend::Bytecode => match Compiler::new().compile_expression(expression) {
+                Ok(function) => self.execute(function).map(|value| (value != Value::Nil).then(|| value.stringify())),
+                Err(errors) => return self.handle_error(&errors, source),
+            },
+        };
+        match result {
+            Ok(Some(text)) => println!("{text}"),
+            Ok(None) => (),
+            Err(runtime_error) => self.handle_runtime_error(&runtime_error, source),
+        }
+    }
+
+    /// Scans, parses and resolves `source`, handing variable depths to the
+    /// interpreter. Errors are reported and yield `None`.
+    fn analyze(&mut self, source: &str) -> Option<Vec<Stmt>> {
+        let tokens = self.scan(source)?;
+
+        // At the prompt a bare expression doesn't need its ';'.
+        let expression = match self.mode {
+            Some(Mode::Repl) => Parser::new(tokens.clone()).parse_expression(),
+            _ => None,
+        };
+        let mut warnings = Vec::new();
+        let statements = match expression {
+            Some(expression) => vec![Stmt::Expr(expression)],
+            None => {
+                let mut parser = Parser::new(tokens);
+                match parser.parse() {
+                    Ok(statements) => {
+                        warnings = parser.take_warnings();
+                        statements
+                    },
+                    Err(errors) => {
+                        self.handle_error(&errors, source);
+                        return None;
+                    },
+                }
+            },
+        };
+
+        let mut resolver = Resolver::new(&mut self.interpreter);
+        if let Err(errors) = resolver.resolve(&statements) {
+            self.handle_error(&errors, source);
+            return None;
+        }
+        if self.warnings_as_errors && !warnings.is_empty() {
+            self.handle_error(&warnings, source);
+            return None;
+        }
+        self.report(&warnings, source);
+        Some(statements)
+    }
+
+    fn scan(&self, source: &str) -> Option<Vec<Token>> {
+        let mut scanner = Scanner::new(source.to_string());
+        match scanner.scan_tokens() {
+            Ok(tokens) => Some(tokens.to_vec()),
+            Err(errors) => {
+                self.handle_error(&errors, source);
+                None
+            },
+        }
+    }
+
+    fn execute(&mut self, function: Rc<Function>) -> Result<Value, LoxError> {
+        if self.dump_bytecode {
+            print!("{}", disassembler::disassemble(&function));
         }
-        else {
-            self.handle_error();
+        self.vm.interpret(function)
+    }
+
+    fn source_name(&self) -> &str {
+        match self.mode.as_ref() {
+            Some(Mode::File(path) | Mode::Load(path)) => path,
+            _ => "<repl>",
+        }
+    }
+
+    fn report(&self, errors: &[LoxError], source: &str) {
+        for lox_error in errors {
+            error::report(lox_error, source, self.source_name(), self.color);
         }
     }
 
-    fn handle_error(&self){
-        if let Mode::File = self.mode.as_ref().unwrap() {
+    fn handle_runtime_error(&self, runtime_error: &LoxError, source: &str){
+        error::report(runtime_error, source, self.source_name(), self.color);
+        if let Some(Mode::File(_)) = self.mode {
+            process::exit(70)
+        }
+    }
+
+    fn handle_error(&self, errors: &[LoxError], source: &str){
+        self.report(errors, source);
+        if let Some(Mode::File(_)) = self.mode {
             process::exit(65)
         }
     }
 }
+
+fn read_source(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Error reading file: {err}");
+        process::exit(1)
+    })
+}
@@ -0,0 +1,611 @@
+use std::rc::Rc;
+
+use crate::{chunk::OpCode, error_handler::LoxError, expr::Expr, literal::Literal, stmt::Stmt, token::{Span, Token}, token_type::TokenType, value::{Function, Value}};
+
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Initializer,
+    Method,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    index: u8,
+    is_local: bool,
+}
+
+/// Everything needed while one function body is being compiled. Nested
+/// function declarations push a new state on top of the enclosing one.
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    loops: Vec<LoopState>,
+}
+
+/// An enclosing loop: the scope depth its body starts from and the jumps
+/// emitted by `break` and `continue`, patched once their targets are known.
+struct LoopState {
+    scope_depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+impl FunctionState {
+    fn new(name: &str, kind: FunctionKind) -> Self {
+        // Slot zero holds the callee, or the receiver inside methods.
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+        FunctionState {
+            function: Function { name: name.to_string(), ..Function::default() },
+            kind,
+            locals: vec![Local { name: receiver.to_string(), depth: 0, is_captured: false }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+/// Compiles resolved statements into bytecode for the `Vm`. The resolver has
+/// already rejected invalid programs, so the only errors left are limits of
+/// the bytecode format.
+pub struct Compiler {
+    states: Vec<FunctionState>,
+    errors: Vec<LoxError>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler { states: Vec::new(), errors: Vec::new() }
+    }
+
+    pub fn compile(&mut self, stmts: &[Stmt]) -> Result<Rc<Function>, Vec<LoxError>> {
+        self.states.push(FunctionState::new("script", FunctionKind::Script));
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+        let end = stmts.last().map(|stmt| stmt.span()).unwrap_or_default();
+        self.end_script(end)
+    }
+
+    /// Compiles a lone expression into a script that returns its value, so
+    /// the REPL can print it.
+    pub fn compile_expression(&mut self, expression: &Expr) -> Result<Rc<Function>, Vec<LoxError>> {
+        self.states.push(FunctionState::new("script", FunctionKind::Script));
+        self.expression(expression);
+        self.emit_op(OpCode::Return, expression.span());
+        self.end_script(expression.span())
+    }
+
+    fn end_script(&mut self, end: Span) -> Result<Rc<Function>, Vec<LoxError>> {
+        let (function, _) = self.end_function(end);
+
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        Ok(Rc::new(function))
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expression) => {
+                self.expression(expression);
+                self.emit_op(OpCode::Pop, expression.span());
+            },
+            Stmt::Print(expression) => {
+                self.expression(expression);
+                self.emit_op(OpCode::Print, stmt.span());
+            },
+            Stmt::Var(name, initializer) => {
+                match initializer {
+                    Some(initializer) => self.expression(initializer),
+                    None => self.emit_op(OpCode::Nil, name.span),
+                }
+                self.define_variable(name);
+            },
+            Stmt::Block(statements, _) => {
+                self.begin_scope();
+                for statement in statements {
+                    self.statement(statement);
+                }
+                self.end_scope(stmt.span());
+            },
+            Stmt::If(condition, then_branch, else_branch) => {
+                let span = condition.span();
+                self.expression(condition);
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                self.emit_op(OpCode::Pop, span);
+                self.statement(then_branch);
+                let else_jump = self.emit_jump(OpCode::Jump, span);
+
+                self.patch_jump(then_jump, span);
+                self.emit_op(OpCode::Pop, span);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+                self.patch_jump(else_jump, span);
+            },
+            Stmt::While(condition, body, increment) => {
+                let span = condition.span();
+                let loop_start = self.chunk_len();
+                self.expression(condition);
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                self.emit_op(OpCode::Pop, span);
+
+                let scope_depth = self.state().scope_depth;
+                self.state_mut().loops.push(LoopState { scope_depth, breaks: Vec::new(), continues: Vec::new() });
+                self.statement(body);
+                let loop_state = self.state_mut().loops.pop().expect("the loop was pushed above");
+
+                for jump in loop_state.continues {
+                    self.patch_jump(jump, span);
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                    self.emit_op(OpCode::Pop, increment.span());
+                }
+                self.emit_loop(loop_start, span);
+
+                self.patch_jump(exit_jump, span);
+                self.emit_op(OpCode::Pop, span);
+                for jump in loop_state.breaks {
+                    self.patch_jump(jump, span);
+                }
+            },
+            Stmt::Break(keyword) | Stmt::Continue(keyword) => {
+                let scope_depth = self.state().loops.last().expect("the parser only allows 'break' and 'continue' inside loops").scope_depth;
+                self.discard_locals(scope_depth, keyword.span);
+                let jump = self.emit_jump(OpCode::Jump, keyword.span);
+                let loop_state = self.state_mut().loops.last_mut().expect("checked above");
+                if matches!(stmt, Stmt::Break(_)) {
+                    loop_state.breaks.push(jump);
+                } else {
+                    loop_state.continues.push(jump);
+                }
+            },
+            Stmt::Function(name, _, _) => {
+                if self.state().scope_depth > 0 {
+                    // Declared before the body is compiled so the function can refer to itself.
+                    self.add_local(&name.lexeme, name.span);
+                    self.function(stmt, FunctionKind::Function);
+                } else {
+                    self.function(stmt, FunctionKind::Function);
+                    self.define_variable(name);
+                }
+            },
+            Stmt::Return(keyword, value) => {
+                if self.state().kind == FunctionKind::Initializer {
+                    self.emit_op(OpCode::GetLocal, keyword.span);
+                    self.emit_byte(0, keyword.span);
+                } else {
+                    match value {
+                        Some(value) => self.expression(value),
+                        None => self.emit_op(OpCode::Nil, keyword.span),
+                    }
+                }
+                self.emit_op(OpCode::Return, stmt.span());
+            },
+            Stmt::Class(name, superclass, methods) => {
+                let constant = self.identifier_constant(name);
+                self.emit_op(OpCode::Class, name.span);
+                self.emit_u16(constant, name.span);
+                self.define_variable(name);
+
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                    self.begin_scope();
+                    self.add_local("super", superclass.span());
+                    self.variable(name, false);
+                    self.emit_op(OpCode::Inherit, superclass.span());
+                }
+
+                self.variable(name, false);
+                for method in methods {
+                    if let Stmt::Function(method_name, _, _) = method {
+                        let kind = if method_name.lexeme == "init" {
+                            FunctionKind::Initializer
+                        } else {
+                            FunctionKind::Method
+                        };
+                        self.function(method, kind);
+                        let constant = self.identifier_constant(method_name);
+                        self.emit_op(OpCode::Method, method_name.span);
+                        self.emit_u16(constant, method_name.span);
+                    }
+                }
+                self.emit_op(OpCode::Pop, name.span);
+
+                if superclass.is_some() {
+                    self.end_scope(name.span);
+                }
+            },
+        }
+    }
+
+    fn expression(&mut self, expression: &Expr) {
+        let span = expression.span();
+        match expression {
+            Expr::Literal(literal, _) => match literal {
+                Literal::Null => self.emit_op(OpCode::Nil, span),
+                Literal::Bool(true) => self.emit_op(OpCode::True, span),
+                Literal::Bool(false) => self.emit_op(OpCode::False, span),
+                _ => self.emit_constant(Value::from(literal), span),
+            },
+            Expr::Grouping(expression, _) => self.expression(expression),
+            Expr::Stringify(expression) => {
+                self.expression(expression);
+                self.emit_op(OpCode::Stringify, span);
+            },
+            Expr::Unary(operator, right) => {
+                self.expression(right);
+                match operator.token_type {
+                    TokenType::Minus => self.emit_op(OpCode::Negate, span),
+                    _ => self.emit_op(OpCode::Not, span),
+                }
+            },
+            Expr::Binary(left, operator, right) => {
+                self.expression(left);
+                self.expression(right);
+                match operator.token_type {
+                    TokenType::Plus => self.emit_op(OpCode::Add, span),
+                    TokenType::Minus => self.emit_op(OpCode::Subtract, span),
+                    TokenType::Star => self.emit_op(OpCode::Multiply, span),
+                    TokenType::Slash => self.emit_op(OpCode::Divide, span),
+                    TokenType::TildeSlash => self.emit_op(OpCode::FloorDivide, span),
+                    TokenType::Percent => self.emit_op(OpCode::Modulo, span),
+                    TokenType::EqualEqual => self.emit_op(OpCode::Equal, span),
+                    TokenType::BangEqual => {
+                        self.emit_op(OpCode::Equal, span);
+                        self.emit_op(OpCode::Not, span);
+                    },
+                    TokenType::Greater => self.emit_op(OpCode::Greater, span),
+                    TokenType::GreaterEqual => self.emit_op(OpCode::GreaterEqual, span),
+                    TokenType::Less => self.emit_op(OpCode::Less, span),
+                    TokenType::LessEqual => self.emit_op(OpCode::LessEqual, span),
+                    _ => unreachable!("the parser only produces binary operators"),
+                }
+            },
+            Expr::Logical(left, operator, right) => {
+                self.expression(left);
+                if operator.token_type == TokenType::And {
+                    let end_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                    self.emit_op(OpCode::Pop, span);
+                    self.expression(right);
+                    self.patch_jump(end_jump, span);
+                } else {
+                    let else_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                    let end_jump = self.emit_jump(OpCode::Jump, span);
+                    self.patch_jump(else_jump, span);
+                    self.emit_op(OpCode::Pop, span);
+                    self.expression(right);
+                    self.patch_jump(end_jump, span);
+                }
+            },
+            Expr::Variable(name, _) => self.variable(name, false),
+            Expr::Assign(name, value, _) => {
+                self.expression(value);
+                self.variable(name, true);
+            },
+            Expr::Call(callee, _, arguments) => match callee.as_ref() {
+                Expr::Get(object, name) => {
+                    self.expression(object);
+                    let argument_count = self.arguments(arguments, span);
+                    let constant = self.identifier_constant(name);
+                    self.emit_op(OpCode::Invoke, span);
+                    self.emit_u16(constant, span);
+                    self.emit_byte(argument_count, span);
+                },
+                Expr::Super(keyword, method, _) => {
+                    self.variable(&Compiler::synthetic(keyword, "this"), false);
+                    let argument_count = self.arguments(arguments, span);
+                    self.variable(keyword, false);
+                    let constant = self.identifier_constant(method);
+                    self.emit_op(OpCode::SuperInvoke, span);
+                    self.emit_u16(constant, span);
+                    self.emit_byte(argument_count, span);
+                },
+                _ => {
+                    self.expression(callee);
+                    let argument_count = self.arguments(arguments, span);
+                    self.emit_op(OpCode::Call, span);
+                    self.emit_byte(argument_count, span);
+                },
+            },
+            Expr::Get(object, name) => {
+                self.expression(object);
+                let constant = self.identifier_constant(name);
+                self.emit_op(OpCode::GetProperty, name.span);
+                self.emit_u16(constant, name.span);
+            },
+            Expr::Set(object, name, value) => {
+                self.expression(object);
+                self.expression(value);
+                let constant = self.identifier_constant(name);
+                self.emit_op(OpCode::SetProperty, name.span);
+                self.emit_u16(constant, name.span);
+            },
+            Expr::This(keyword, _) => self.variable(keyword, false),
+            Expr::List(_, items, _) => {
+                for item in items {
+                    self.expression(item);
+                }
+                let count = u16::try_from(items.len()).unwrap_or_else(|_| {
+                    self.error(span, "Too many elements in a list literal.");
+                    0
+                });
+                self.emit_op(OpCode::BuildList, span);
+                self.emit_u16(count, span);
+            },
+            Expr::Map(_, entries, _) => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                let count = u16::try_from(entries.len()).unwrap_or_else(|_| {
+                    self.error(span, "Too many entries in a map literal.");
+                    0
+                });
+                self.emit_op(OpCode::BuildMap, span);
+                self.emit_u16(count, span);
+            },
+            Expr::Index(object, _, index) => {
+                self.expression(object);
+                self.expression(index);
+                self.emit_op(OpCode::GetIndex, span);
+            },
+            Expr::IndexSet(object, _, index, value) => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+                self.emit_op(OpCode::SetIndex, span);
+            },
+            Expr::Super(keyword, method, _) => {
+                self.variable(&Compiler::synthetic(keyword, "this"), false);
+                self.variable(keyword, false);
+                let constant = self.identifier_constant(method);
+                self.emit_op(OpCode::GetSuper, method.span);
+                self.emit_u16(constant, method.span);
+            },
+        }
+    }
+
+    fn arguments(&mut self, arguments: &[Expr], span: Span) -> u8 {
+        for argument in arguments {
+            self.expression(argument);
+        }
+        u8::try_from(arguments.len()).unwrap_or_else(|_| {
+            self.error(span, "Can't have more than 255 arguments.");
+            0
+        })
+    }
+
+    /// Compiles a function declaration and leaves a closure over it on the stack.
+    fn function(&mut self, declaration: &Stmt, kind: FunctionKind) {
+        if let Stmt::Function(name, params, body) = declaration {
+            self.states.push(FunctionState::new(&name.lexeme, kind));
+            self.state_mut().function.arity = params.len();
+            self.begin_scope();
+            for param in params {
+                self.add_local(&param.lexeme, param.span);
+            }
+            for stmt in body {
+                self.statement(stmt);
+            }
+
+            let (function, upvalues) = self.end_function(declaration.span());
+            let constant = self.make_constant(Value::Function(Rc::new(function)), name.span);
+            self.emit_op(OpCode::Closure, name.span);
+            self.emit_u16(constant, name.span);
+            for upvalue in upvalues {
+                self.emit_byte(upvalue.is_local as u8, name.span);
+                self.emit_byte(upvalue.index, name.span);
+            }
+        }
+    }
+
+    fn end_function(&mut self, span: Span) -> (Function, Vec<UpvalueRef>) {
+        self.emit_return(span);
+        let state = self.states.pop().expect("a function is being compiled");
+        let mut function = state.function;
+        function.upvalue_count = state.upvalues.len();
+        (function, state.upvalues)
+    }
+
+    fn emit_return(&mut self, span: Span) {
+        if self.state().kind == FunctionKind::Initializer {
+            self.emit_op(OpCode::GetLocal, span);
+            self.emit_byte(0, span);
+        } else {
+            self.emit_op(OpCode::Nil, span);
+        }
+        self.emit_op(OpCode::Return, span);
+    }
+
+    /// Emits a read, or a write of the value on top of the stack, of the
+    /// variable `name`, looking it up as a local, a captured variable or a global.
+    fn variable(&mut self, name: &Token, assign: bool) {
+        let current = self.states.len() - 1;
+        let (op, operand) = if let Some(slot) = self.resolve_local(current, &name.lexeme) {
+            (if assign { OpCode::SetLocal } else { OpCode::GetLocal }, slot)
+        } else if let Some(index) = self.resolve_upvalue(current, &name.lexeme, name.span) {
+            (if assign { OpCode::SetUpvalue } else { OpCode::GetUpvalue }, index)
+        } else {
+            let constant = self.identifier_constant(name);
+            self.emit_op(if assign { OpCode::SetGlobal } else { OpCode::GetGlobal }, name.span);
+            self.emit_u16(constant, name.span);
+            return;
+        };
+        self.emit_op(op, name.span);
+        self.emit_byte(operand, name.span);
+    }
+
+    fn define_variable(&mut self, name: &Token) {
+        if self.state().scope_depth > 0 {
+            self.add_local(&name.lexeme, name.span);
+            return;
+        }
+        let constant = self.identifier_constant(name);
+        self.emit_op(OpCode::DefineGlobal, name.span);
+        self.emit_u16(constant, name.span);
+    }
+
+    fn resolve_local(&self, state: usize, name: &str) -> Option<u8> {
+        self.states[state].locals.iter().rposition(|local| local.name == name).map(|slot| slot as u8)
+    }
+
+    fn resolve_upvalue(&mut self, state: usize, name: &str, span: Span) -> Option<u8> {
+        if state == 0 {
+            return None;
+        }
+        if let Some(slot) = self.resolve_local(state - 1, name) {
+            self.states[state - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(state, UpvalueRef { index: slot, is_local: true }, span));
+        }
+        let index = self.resolve_upvalue(state - 1, name, span)?;
+        Some(self.add_upvalue(state, UpvalueRef { index, is_local: false }, span))
+    }
+
+    fn add_upvalue(&mut self, state: usize, upvalue: UpvalueRef, span: Span) -> u8 {
+        let upvalues = &mut self.states[state].upvalues;
+        if let Some(index) = upvalues.iter().position(|existing| *existing == upvalue) {
+            return index as u8;
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            self.error(span, "Too many closure variables in function.");
+            return 0;
+        }
+        upvalues.push(upvalue);
+        (upvalues.len() - 1) as u8
+    }
+
+    fn add_local(&mut self, name: &str, span: Span) {
+        if self.state().locals.len() == MAX_LOCALS {
+            self.error(span, "Too many local variables in function.");
+            return;
+        }
+        let depth = self.state().scope_depth;
+        self.state_mut().locals.push(Local { name: name.to_string(), depth, is_captured: false });
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self, span: Span) {
+        self.state_mut().scope_depth -= 1;
+        let depth = self.state().scope_depth;
+        while let Some(local) = self.state().locals.last().filter(|local| local.depth > depth) {
+            let op = if local.is_captured { OpCode::CloseUpvalue } else { OpCode::Pop };
+            self.emit_op(op, span);
+            self.state_mut().locals.pop();
+        }
+    }
+
+    /// Pops the locals deeper than `depth` without forgetting them, for
+    /// jumps that leave their scopes early.
+    fn discard_locals(&mut self, depth: usize, span: Span) {
+        let ops: Vec<OpCode> = self.state().locals.iter().rev()
+            .take_while(|local| local.depth > depth)
+            .map(|local| if local.is_captured { OpCode::CloseUpvalue } else { OpCode::Pop })
+            .collect();
+        for op in ops {
+            self.emit_op(op, span);
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &Token) -> u16 {
+        self.make_constant(Value::String(Rc::from(name.lexeme.as_str())), name.span)
+    }
+
+    fn make_constant(&mut self, value: Value, span: Span) -> u16 {
+        let index = self.state_mut().function.chunk.add_constant(value);
+        u16::try_from(index).unwrap_or_else(|_| {
+            self.error(span, "Too many constants in one chunk.");
+            0
+        })
+    }
+
+    fn emit_constant(&mut self, value: Value, span: Span) {
+        let constant = self.make_constant(value, span);
+        self.emit_op(OpCode::Constant, span);
+        self.emit_u16(constant, span);
+    }
+
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit_op(op, span);
+        self.emit_u16(u16::MAX, span);
+        self.chunk_len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize, span: Span) {
+        let jump = self.chunk_len() - offset - 2;
+        let jump = u16::try_from(jump).unwrap_or_else(|_| {
+            self.error(span, "Too much code to jump over.");
+            0
+        });
+        let code = &mut self.state_mut().function.chunk.code;
+        code[offset..offset + 2].copy_from_slice(&jump.to_be_bytes());
+    }
+
+    fn emit_loop(&mut self, loop_start: usize, span: Span) {
+        self.emit_op(OpCode::Loop, span);
+        let offset = self.chunk_len() - loop_start + 2;
+        let offset = u16::try_from(offset).unwrap_or_else(|_| {
+            self.error(span, "Loop body too large.");
+            0
+        });
+        self.emit_u16(offset, span);
+    }
+
+    fn emit_op(&mut self, op: OpCode, span: Span) {
+        self.state_mut().function.chunk.write_op(op, span);
+    }
+
+    fn emit_byte(&mut self, byte: u8, span: Span) {
+        self.state_mut().function.chunk.write(byte, span);
+    }
+
+    fn emit_u16(&mut self, value: u16, span: Span) {
+        for byte in value.to_be_bytes() {
+            self.emit_byte(byte, span);
+        }
+    }
+
+    fn chunk_len(&self) -> usize {
+        self.state().function.chunk.code.len()
+    }
+
+    fn state(&self) -> &FunctionState {
+        self.states.last().expect("a function is being compiled")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.states.last_mut().expect("a function is being compiled")
+    }
+
+    /// A token standing in for a name the source never spells out, like the
+    /// receiver of a `super` call.
+    fn synthetic(token: &Token, name: &str) -> Token {
+        Token::new(TokenType::Identifier, name.to_string(), None, token.span)
+    }
+
+    fn error(&mut self, span: Span, message: &str) {
+        self.errors.push(LoxError::compile(span, message));
+    }
+}
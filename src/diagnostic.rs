@@ -0,0 +1,102 @@
+use std::fmt::Write;
+
+use crate::error_handler::LoxError;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const YELLOW: &str = "\x1b[1;33m";
+
+/// Renders a `LoxError` the way rustc does: a header with the error code,
+/// the offending source lines and `^^^` under the span.
+pub struct Diagnostic<'a> {
+    source: &'a str,
+    path: &'a str,
+    color: bool,
+}
+
+struct SnippetLine<'a> {
+    number: usize,
+    text: &'a str,
+    padding: String,
+    width: usize,
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn new(source: &'a str, path: &'a str, color: bool) -> Self {
+        Diagnostic { source, path, color }
+    }
+
+    pub fn render(&self, error: &LoxError) -> String {
+        let span = error.span();
+        let lines = self.snippet(span.start, span.end, span.line);
+        let gutter = lines.last().map_or(1, |line| line.number.to_string().len());
+        let pad = " ".repeat(gutter);
+
+        let (label, accent) = if error.is_warning() { ("warning", YELLOW) } else { ("error", RED) };
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{}{label}[{}]{}{}: {}{}", self.paint(accent), error.code(), self.paint(RESET), self.paint(BOLD), error.message(), self.paint(RESET));
+        let _ = writeln!(out, "{pad}{}-->{} {}:{}:{}", self.paint(BLUE), self.paint(RESET), self.path, span.line, span.column);
+        // Compiled programs are run without their source, so there is nothing to quote.
+        if !self.source.is_empty() {
+            let _ = writeln!(out, "{pad} {}|{}", self.paint(BLUE), self.paint(RESET));
+            for line in &lines {
+                let _ = writeln!(out, "{}{:>gutter$} |{} {}", self.paint(BLUE), line.number, self.paint(RESET), line.text);
+                let _ = writeln!(out, "{pad} {}|{} {}{}{}{}", self.paint(BLUE), self.paint(RESET), line.padding, self.paint(accent), "^".repeat(line.width), self.paint(RESET));
+            }
+        }
+
+        if let Some(help) = error.help() {
+            let _ = writeln!(out, "{pad} {}|{}", self.paint(BLUE), self.paint(RESET));
+            let _ = writeln!(out, "{pad} {}={} {}help{}: {}", self.paint(BLUE), self.paint(RESET), self.paint(BOLD), self.paint(RESET), help);
+        }
+        out
+    }
+
+    fn paint(&self, code: &'static str) -> &'static str {
+        if self.color { code } else { "" }
+    }
+
+    /// Every source line touched by the byte range `start..end`, with the
+    /// part of the line that falls inside the range.
+    fn snippet(&self, start: usize, end: usize, first_line: usize) -> Vec<SnippetLine<'a>> {
+        let start = self.floor_char_boundary(start);
+        let end = self.floor_char_boundary(end).max(start);
+
+        let mut lines = Vec::new();
+        let mut line_start = self.source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let mut number = first_line;
+
+        loop {
+            let line_end = self.source[line_start..].find('\n').map_or(self.source.len(), |index| line_start + index);
+            let indent = self.source[line_start..line_end].len() - self.source[line_start..line_end].trim_start().len();
+            let from = if start < line_start { line_start + indent } else { start };
+            let to = end.min(line_end).max(from);
+
+            let padding = self.source[line_start..from]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let width = self.source[from..to].chars().count().max(1);
+            let text = self.source[line_start..line_end].trim_end_matches('\r');
+            lines.push(SnippetLine { number, text, padding, width });
+
+            if line_end >= end || line_end == self.source.len() {
+                break;
+            }
+            line_start = line_end + 1;
+            number += 1;
+        }
+        lines
+    }
+
+    fn floor_char_boundary(&self, index: usize) -> usize {
+        let mut index = index.min(self.source.len());
+        while !self.source.is_char_boundary(index) {
+            index -= 1;
+        }
+        index
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use crate::{chunk::{Chunk, OpCode}, value::{Function, Value}};
+
+/// Produces a clox-style listing of `function` followed by the listings of
+/// every function nested in it.
+pub fn disassemble(function: &Function) -> String {
+    let mut out = String::new();
+    disassemble_function(function, &mut out);
+    out
+}
+
+fn disassemble_function(function: &Function, out: &mut String) {
+    let name = if function.name == "script" { String::from("<script>") } else { function.name.clone() };
+    disassemble_chunk(&function.chunk, &name, out);
+
+    for constant in &function.chunk.constants {
+        if let Value::Function(nested) = constant {
+            let _ = writeln!(out);
+            disassemble_function(nested, out);
+        }
+    }
+}
+
+fn disassemble_chunk(chunk: &Chunk, name: &str, out: &mut String) {
+    let _ = writeln!(out, "== {name} ==");
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, out);
+    }
+}
+
+/// Writes the instruction at `offset` and returns the offset of the next one.
+fn disassemble_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let _ = write!(out, "{offset:04} ");
+    let line = chunk.span_at(offset).line;
+    if offset > 0 && line == chunk.span_at(offset - 1).line {
+        let _ = write!(out, "   | ");
+    } else {
+        let _ = write!(out, "{line:4} ");
+    }
+
+    let byte = chunk.code[offset];
+    let Some(op) = OpCode::from_byte(byte) else {
+        let _ = writeln!(out, "Unknown opcode {byte}");
+        return offset + 1;
+    };
+
+    match op {
+        OpCode::Constant | OpCode::GetGlobal | OpCode::DefineGlobal | OpCode::SetGlobal
+        | OpCode::GetProperty | OpCode::SetProperty | OpCode::GetSuper | OpCode::Class | OpCode::Method => {
+            let constant = chunk.read_u16(offset + 1) as usize;
+            let _ = writeln!(out, "{:<16} {constant:4} '{}'", name(op), constant_text(chunk, constant));
+            offset + 3
+        },
+        OpCode::GetLocal | OpCode::SetLocal | OpCode::GetUpvalue | OpCode::SetUpvalue | OpCode::Call => {
+            let _ = writeln!(out, "{:<16} {:4}", name(op), chunk.code[offset + 1]);
+            offset + 2
+        },
+        OpCode::BuildList | OpCode::BuildMap => {
+            let _ = writeln!(out, "{:<16} {:4}", name(op), chunk.read_u16(offset + 1));
+            offset + 3
+        },
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            let target = if op == OpCode::Loop { offset + 3 - jump } else { offset + 3 + jump };
+            let _ = writeln!(out, "{:<16} {offset:4} -> {target}", name(op));
+            offset + 3
+        },
+        OpCode::Invoke | OpCode::SuperInvoke => {
+            let constant = chunk.read_u16(offset + 1) as usize;
+            let argument_count = chunk.code[offset + 3];
+            let _ = writeln!(out, "{:<16} ({argument_count} args) {constant:4} '{}'", name(op), constant_text(chunk, constant));
+            offset + 4
+        },
+        OpCode::Closure => {
+            let constant = chunk.read_u16(offset + 1) as usize;
+            let _ = writeln!(out, "{:<16} {constant:4} {}", name(op), constant_text(chunk, constant));
+
+            let mut offset = offset + 3;
+            if let Value::Function(function) = &chunk.constants[constant] {
+                for _ in 0..function.upvalue_count {
+                    let kind = if chunk.code[offset] == 1 { "local" } else { "upvalue" };
+                    let index = chunk.code[offset + 1];
+                    let _ = writeln!(out, "{offset:04}    |                     {kind} {index}");
+                    offset += 2;
+                }
+            }
+            offset
+        },
+        _ => {
+            let _ = writeln!(out, "{}", name(op));
+            offset + 1
+        },
+    }
+}
+
+fn constant_text(chunk: &Chunk, index: usize) -> String {
+    match &chunk.constants[index] {
+        Value::Function(function) => format!("<fn {}>", function.name),
+        constant => constant.stringify(),
+    }
+}
+
+fn name(op: OpCode) -> &'static str {
+    match op {
+        OpCode::Constant => "OP_CONSTANT",
+        OpCode::Nil => "OP_NIL",
+        OpCode::True => "OP_TRUE",
+        OpCode::False => "OP_FALSE",
+        OpCode::Pop => "OP_POP",
+        OpCode::GetLocal => "OP_GET_LOCAL",
+        OpCode::SetLocal => "OP_SET_LOCAL",
+        OpCode::GetGlobal => "OP_GET_GLOBAL",
+        OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+        OpCode::SetGlobal => "OP_SET_GLOBAL",
+        OpCode::GetUpvalue => "OP_GET_UPVALUE",
+        OpCode::SetUpvalue => "OP_SET_UPVALUE",
+        OpCode::GetProperty => "OP_GET_PROPERTY",
+        OpCode::SetProperty => "OP_SET_PROPERTY",
+        OpCode::GetSuper => "OP_GET_SUPER",
+        OpCode::Equal => "OP_EQUAL",
+        OpCode::Greater => "OP_GREATER",
+        OpCode::GreaterEqual => "OP_GREATER_EQUAL",
+        OpCode::Less => "OP_LESS",
+        OpCode::LessEqual => "OP_LESS_EQUAL",
+        OpCode::Add => "OP_ADD",
+        OpCode::Subtract => "OP_SUBTRACT",
+        OpCode::Multiply => "OP_MULTIPLY",
+        OpCode::Divide => "OP_DIVIDE",
+        OpCode::FloorDivide => "OP_FLOOR_DIVIDE",
+        OpCode::Modulo => "OP_MODULO",
+        OpCode::Not => "OP_NOT",
+        OpCode::Negate => "OP_NEGATE",
+        OpCode::Print => "OP_PRINT",
+        OpCode::Jump => "OP_JUMP",
+        OpCode::JumpIfFalse => "OP_JUMP_IF_FALSE",
+        OpCode::Loop => "OP_LOOP",
+        OpCode::Call => "OP_CALL",
+        OpCode::Invoke => "OP_INVOKE",
+        OpCode::SuperInvoke => "OP_SUPER_INVOKE",
+        OpCode::Closure => "OP_CLOSURE",
+        OpCode::CloseUpvalue => "OP_CLOSE_UPVALUE",
+        OpCode::Return => "OP_RETURN",
+        OpCode::BuildList => "OP_BUILD_LIST",
+        OpCode::BuildMap => "OP_BUILD_MAP",
+        OpCode::Stringify => "OP_STRINGIFY",
+        OpCode::GetIndex => "OP_GET_INDEX",
+        OpCode::SetIndex => "OP_SET_INDEX",
+        OpCode::Class => "OP_CLASS",
+        OpCode::Inherit => "OP_INHERIT",
+        OpCode::Method => "OP_METHOD",
+    }
+}
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, rc::Rc, cell::RefCell};
 
-use crate::{literal::Literal, token::Token, error_handler::error};
+use crate::{literal::Literal, token::Token, error_handler::LoxError};
 
 #[derive(Clone, Debug)]
 pub struct Environment {
@@ -35,30 +35,55 @@ impl Environment {
         self.values.insert(name, value);
     }
 
-    pub fn get(&self, name: &Token) -> Option<Literal> {
+    /// The names defined directly in this environment, not its enclosing ones.
+    pub fn names(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+
+    /// The names and values defined directly in this environment.
+    pub fn bindings(&self) -> Vec<(String, Literal)> {
+        self.values.iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Literal, LoxError> {
 
-        if self.values.contains_key(&name.lexeme) {
-            return Some(self.values.get(&name.lexeme).unwrap().clone());
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
         }
         
         match &self.enclosing {
-            Some(environment) => return environment.borrow().get(name),
-            None => return {
-                error::runtime_error(name, format!("Undefined variable {}.", name.lexeme).as_str());
-                None
-            }
+            Some(environment) => environment.borrow().get(name),
+            None => Err(LoxError::runtime(name, &format!("Undefined variable '{}'.", name.lexeme))
+                .with_help(&format!("declare it with 'var {} = ...;' before using it", name.lexeme))),
         }
 
     }
 
-    pub fn assign(&mut self, name: &Token, value: &Literal) {
+    pub fn get_at(&self, distance: usize, name: &str) -> Option<Literal> {
+        if distance == 0 {
+            return self.values.get(name).cloned();
+        }
+        self.enclosing.as_ref()?.borrow().get_at(distance - 1, name)
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: &Literal) {
+        if distance == 0 {
+            self.values.insert(name.lexeme.clone(), value.clone());
+            return;
+        }
+        if let Some(environment) = &self.enclosing {
+            environment.borrow_mut().assign_at(distance - 1, name, value);
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: &Literal) -> Result<(), LoxError> {
         if let Some(name) = self.values.get_mut(&name.lexeme) {
             *name = value.clone();
-            return;
+            return Ok(());
         }
         match &mut self.enclosing {
             Some(environment) => environment.borrow_mut().assign(name, value),
-            None => error::runtime_error(name, &format!("Undefined variable '{}'.",name.lexeme)),
+            None => Err(LoxError::runtime(name, &format!("Undefined variable '{}'.",name.lexeme))),
         }
     }
 }
\ No newline at end of file
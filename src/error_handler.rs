@@ -1,20 +1,158 @@
-pub mod error{
-    use crate::{token::Token, token_type::TokenType};
+use std::fmt;
+
+use crate::{token::{Token, Span}, token_type::TokenType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoxError {
+    Scan(Box<ErrorDetails>),
+    Parse(Box<ErrorDetails>),
+    Resolve(Box<ErrorDetails>),
+    Runtime(Box<ErrorDetails>),
+    Compile(Box<ErrorDetails>),
+    /// Something suspicious that doesn't stop the program from running,
+    /// unless warnings are treated as errors.
+    Warning(Box<ErrorDetails>),
+}
+
+/// What every kind of error carries. It lives behind a `Box` so a
+/// `LoxError` is only two words: runtime errors are returned through every
+/// frame of the tree-walker's recursion, and a bigger `Result` makes each
+/// of those frames bigger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorDetails {
+    token: Token,
+    span: Span,
+    message: String,
+    help: Option<String>,
+}
+
+impl ErrorDetails {
+    fn new(token: Token, message: &str) -> Box<Self> {
+        Box::new(ErrorDetails { span: token.span, token, message: message.to_string(), help: None })
+    }
+}
+
+impl LoxError {
+    pub fn scan(token: Token, message: &str) -> Self {
+        LoxError::Scan(ErrorDetails::new(token, message))
+    }
+
+    pub fn parse(token: Token, message: &str) -> Self {
+        LoxError::Parse(ErrorDetails::new(token, message))
+    }
+
+    pub fn resolve(token: Token, message: &str) -> Self {
+        LoxError::Resolve(ErrorDetails::new(token, message))
+    }
+
+    pub fn runtime(token: &Token, message: &str) -> Self {
+        LoxError::Runtime(ErrorDetails::new(token.clone(), message))
+    }
+
+    pub fn warning(token: Token, message: &str) -> Self {
+        LoxError::Warning(ErrorDetails::new(token, message))
+    }
+
+    pub fn is_warning(&self) -> bool {
+        matches!(self, LoxError::Warning(_))
+    }
+
+    /// Compile errors come from limits of the bytecode format rather than from
+    /// a particular token, so they only carry the span of the offending code.
+    pub fn compile(span: Span, message: &str) -> Self {
+        let token = Token::new(TokenType::Error, String::new(), None, span);
+        LoxError::Compile(ErrorDetails::new(token, message))
+    }
 
-    pub fn error(token: Token, message: &str){
-        if token.token_type == TokenType::Eof {
-            report(token.line, "at end", message);
+    /// Points the error at `span` instead of the span of its token.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.details_mut().span = span;
+        self
+    }
+
+    /// Attaches a "help:" note shown below the source snippet.
+    pub fn with_help(mut self, note: &str) -> Self {
+        self.details_mut().help = Some(note.to_string());
+        self
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoxError::Scan(_) => "E0001",
+            LoxError::Parse(_) => "E0002",
+            LoxError::Resolve(_) => "E0003",
+            LoxError::Runtime(_) => "E0004",
+            LoxError::Compile(_) => "E0005",
+            LoxError::Warning(_) => "W0001",
+        }
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.details().token
+    }
+
+    pub fn span(&self) -> Span {
+        self.details().span
+    }
+
+    pub fn message(&self) -> &str {
+        &self.details().message
+    }
+
+    pub fn help(&self) -> Option<&str> {
+        self.details().help.as_deref()
+    }
+
+    fn details(&self) -> &ErrorDetails {
+        match self {
+            LoxError::Scan(details)
+            | LoxError::Parse(details)
+            | LoxError::Resolve(details)
+            | LoxError::Runtime(details)
+            | LoxError::Compile(details)
+            | LoxError::Warning(details) => details,
         }
-        else{
-            report(token.line, &format!("at '{}'", token.lexeme), message);
+    }
+
+    fn details_mut(&mut self) -> &mut ErrorDetails {
+        match self {
+            LoxError::Scan(details)
+            | LoxError::Parse(details)
+            | LoxError::Resolve(details)
+            | LoxError::Runtime(details)
+            | LoxError::Compile(details)
+            | LoxError::Warning(details) => details,
         }
     }
+}
 
-    pub fn runtime_error(operator: &Token, message: &str){
-        eprintln!("{}\n[line {}]", message, operator.line);
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Span { line, column, .. } = self.span();
+        let message = self.message();
+        match self {
+            LoxError::Scan(_) | LoxError::Compile(_) => write!(f, "[line {line}, column {column}] Error: {message}"),
+            LoxError::Runtime(_) => write!(f, "{message}\n[line {line}, column {column}]"),
+            LoxError::Warning(_) => write!(f, "[line {line}, column {column}] Warning at '{}': {message}", self.token().lexeme),
+            LoxError::Parse(_) | LoxError::Resolve(_) => {
+                let token = self.token();
+                if token.token_type == TokenType::Eof {
+                    write!(f, "[line {line}, column {column}] Error at end: {message}")
+                }
+                else {
+                    write!(f, "[line {line}, column {column}] Error at '{}': {message}", token.lexeme)
+                }
+            }
+        }
     }
+}
+
+pub mod error{
+    use super::LoxError;
+    use crate::diagnostic::Diagnostic;
 
-    fn report(line: usize, location: &str, message: &str){
-        eprintln!("[line {line}] Error {location}: {message}");
+    pub fn report(error: &LoxError, source: &str, path: &str, color: bool){
+        let diagnostic = Diagnostic::new(source, path, color);
+        eprint!("{}", diagnostic.render(error));
     }
-}
\ No newline at end of file
+}
@@ -1,13 +1,57 @@
-use crate::{token::Token, literal::{Literal}};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{token::{Token, Span}, literal::{Literal}};
+
+pub type ExprId = usize;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub fn next_id() -> ExprId {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr{
-    Assign(Token, Box<Expr>),
+    Assign(Token, Box<Expr>, ExprId),
     Binary(Box<Expr>, Token, Box<Expr>),
     Unary(Token, Box<Expr>),
-    Literal(Literal),
+    Literal(Literal, Span),
     Logical(Box<Expr>, Token, Box<Expr>),
-    Variable(Token),
-    Grouping(Box<Expr>),
-    Call(Box<Expr>, Token, Vec<Option<Box<Expr>>>)
-}
\ No newline at end of file
+    Variable(Token, ExprId),
+    /// The expression and the span from `(` to `)`.
+    Grouping(Box<Expr>, Span),
+    /// Converts the value to its printed form; produced by string interpolation.
+    Stringify(Box<Expr>),
+    Call(Box<Expr>, Token, Vec<Expr>),
+    Get(Box<Expr>, Token),
+    Set(Box<Expr>, Token, Box<Expr>),
+    This(Token, ExprId),
+    Super(Token, Token, ExprId),
+    List(Token, Vec<Expr>, Token),
+    Map(Token, Vec<(Expr, Expr)>, Token),
+    Index(Box<Expr>, Token, Box<Expr>),
+    IndexSet(Box<Expr>, Token, Box<Expr>, Box<Expr>)
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign(name, value, _) => name.span.merge(value.span()),
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => left.span().merge(right.span()),
+            Expr::Unary(operator, right) => operator.span.merge(right.span()),
+            Expr::Literal(_, span) => *span,
+            Expr::Variable(name, _) => name.span,
+            Expr::Grouping(_, span) => *span,
+            Expr::Stringify(expression) => expression.span(),
+            Expr::Call(callee, paren, _) => callee.span().merge(paren.span),
+            Expr::Get(object, name) => object.span().merge(name.span),
+            Expr::Set(object, _, value) => object.span().merge(value.span()),
+            Expr::This(keyword, _) => keyword.span,
+            Expr::Super(keyword, method, _) => keyword.span.merge(method.span),
+            Expr::List(open, _, close) => open.span.merge(close.span),
+            Expr::Map(open, _, close) => open.span.merge(close.span),
+            Expr::Index(object, bracket, _) => object.span().merge(bracket.span),
+            Expr::IndexSet(object, _, _, value) => object.span().merge(value.span()),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{parser::Parser, scanner::Scanner, token::{Token, Span}, token_type::TokenType, error_handler::LoxError};
+
+const INDENT: &str = "  ";
+
+/// How a token is laid out, beyond its type.
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    Plain,
+    /// `-` or `!` applied to the operand after it.
+    Unary,
+    BlockOpen,
+    BlockClose,
+    MapOpen,
+    MapClose,
+}
+
+/// Why a file couldn't be formatted.
+pub enum FormatError {
+    /// The source doesn't scan or parse.
+    Invalid(Vec<LoxError>),
+    /// Laying the tokens out again would have changed them. This is a bug in
+    /// the formatter; the file must be left as it is.
+    Changed,
+}
+
+/// Reformats Lox source: one statement per line, blocks indented by two
+/// spaces and single spaces around binary operators. Comments are kept, and
+/// so is one blank line wherever the source had any between statements.
+///
+/// This works on tokens rather than the syntax tree, so `for` loops, string
+/// escapes and number literals come out exactly as they were written. The
+/// source still has to parse: the layout rules assume a valid program.
+pub fn format(source: &str) -> Result<String, FormatError> {
+    let mut scanner = Scanner::new(source.to_string());
+    let tokens = scanner.scan_tokens().map_err(FormatError::Invalid)?.clone();
+    let comments = scanner.comments().to_vec();
+    Parser::new(tokens.clone()).parse().map_err(FormatError::Invalid)?;
+
+    let mut formatter = Formatter::new(source, comments);
+    for token in &tokens {
+        formatter.token(token);
+    }
+    let formatted = formatter.finish();
+
+    // Only whitespace may change.
+    if shape(source) != shape(&formatted) {
+        return Err(FormatError::Changed);
+    }
+    Ok(formatted)
+}
+
+/// The tokens and then the comments of `source`, ignoring layout.
+fn shape(source: &str) -> Option<Vec<String>> {
+    let mut scanner = Scanner::new(source.to_string());
+    let mut shape: Vec<String> = scanner.scan_tokens().ok()?.iter()
+        .map(|token| format!("{:?} {}", token.token_type, token.lexeme))
+        .collect();
+    shape.extend(scanner.comments().iter().map(|span| source[span.start..span.end].trim_end().to_string()));
+    Some(shape)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<Span>,
+    next_comment: usize,
+    out: String,
+    indent: usize,
+    at_line_start: bool,
+    /// A line break is owed before the next token or comment.
+    pending_newline: bool,
+    /// An inline `/* */` comment was just written and needs a space after it.
+    pending_space: bool,
+    /// The source line the last token or comment ended on, 0 before the first.
+    last_line: usize,
+    /// One entry per open `{`: true for a block, false for a map literal.
+    braces: Vec<bool>,
+    /// Open parentheses and brackets. A `;` inside them is part of a `for`
+    /// header and doesn't end the line.
+    nesting: usize,
+    previous: Option<(TokenType, Role)>,
+    comment_since_token: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str, comments: Vec<Span>) -> Self {
+        Formatter {
+            source,
+            comments,
+            next_comment: 0,
+            out: String::new(),
+            indent: 0,
+            at_line_start: true,
+            pending_newline: false,
+            pending_space: false,
+            last_line: 0,
+            braces: Vec::new(),
+            nesting: 0,
+            previous: None,
+            comment_since_token: false,
+        }
+    }
+
+    fn token(&mut self, token: &Token) {
+        self.comments_before(token.span.start);
+        if token.token_type == TokenType::Eof {
+            return;
+        }
+
+        let role = self.role(token);
+        match role {
+            Role::BlockOpen => self.braces.push(true),
+            Role::MapOpen => self.braces.push(false),
+            Role::BlockClose | Role::MapClose => {
+                self.braces.pop();
+            },
+            _ => (),
+        }
+        if role == Role::BlockClose {
+            self.indent = self.indent.saturating_sub(1);
+            // An empty block stays on one line: `{}`.
+            if self.previous_role() == Some(Role::BlockOpen) && !self.comment_since_token {
+                self.pending_newline = false;
+            }
+        }
+        if token.token_type == TokenType::Else && self.previous_role() == Some(Role::BlockClose) {
+            self.pending_newline = false;
+        }
+
+        if self.pending_newline {
+            let blank = token.span.line > self.last_line + 1
+                && self.previous_role() != Some(Role::BlockOpen)
+                && role != Role::BlockClose;
+            self.newline(blank);
+        } else if self.pending_space || self.needs_space(token, role) {
+            self.out.push(' ');
+        }
+        self.write(&token.lexeme);
+        self.pending_space = false;
+
+        match token.token_type {
+            TokenType::LeftBrace if role == Role::BlockOpen => {
+                self.indent += 1;
+                self.pending_newline = true;
+            },
+            TokenType::RightBrace if role == Role::BlockClose => self.pending_newline = true,
+            TokenType::Semicolon if self.nesting == 0 => self.pending_newline = true,
+            TokenType::LeftParen | TokenType::LeftBracket => self.nesting += 1,
+            TokenType::RightParen | TokenType::RightBracket => self.nesting = self.nesting.saturating_sub(1),
+            _ => (),
+        }
+        self.last_line = token.span.line + token.lexeme.matches('\n').count();
+        self.previous = Some((token.token_type.clone(), role));
+        self.comment_since_token = false;
+    }
+
+    /// Writes the comments that start before `offset`. A comment on the
+    /// same line as the code before it stays there; any other goes on a line
+    /// of its own.
+    fn comments_before(&mut self, offset: usize) {
+        while let Some(&span) = self.comments.get(self.next_comment) {
+            if span.start >= offset {
+                break;
+            }
+            self.next_comment += 1;
+            let text = self.source[span.start..span.end].trim_end();
+
+            if self.last_line == span.line {
+                if !self.at_line_start {
+                    self.out.push(' ');
+                }
+                self.write(text);
+                self.pending_space = true;
+            } else {
+                if !self.out.is_empty() {
+                    let blank = span.line > self.last_line + 1 && self.previous_role() != Some(Role::BlockOpen);
+                    self.newline(blank);
+                }
+                self.write(text);
+                self.pending_newline = true;
+            }
+            if text.starts_with("//") {
+                self.pending_newline = true;
+            }
+            self.last_line = span.line + text.matches('\n').count();
+            self.comment_since_token = true;
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.comments_before(usize::MAX);
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    fn role(&self, token: &Token) -> Role {
+        match token.token_type {
+            TokenType::LeftBrace if self.starts_block() => Role::BlockOpen,
+            TokenType::LeftBrace => Role::MapOpen,
+            TokenType::RightBrace if self.braces.last() == Some(&false) => Role::MapClose,
+            TokenType::RightBrace => Role::BlockClose,
+            TokenType::Bang => Role::Unary,
+            TokenType::Minus if !self.follows_operand() => Role::Unary,
+            _ => Role::Plain,
+        }
+    }
+
+    /// Whether a `{` here opens a block rather than a map literal: it does
+    /// wherever a statement may start, and after the `)` of a header or
+    /// parameter list or the name of a class.
+    fn starts_block(&self) -> bool {
+        match &self.previous {
+            None => true,
+            Some((TokenType::Semicolon | TokenType::RightParen | TokenType::Else | TokenType::Identifier, _)) => true,
+            Some((_, role)) => matches!(role, Role::BlockOpen | Role::BlockClose),
+        }
+    }
+
+    fn follows_operand(&self) -> bool {
+        match &self.previous {
+            Some((token_type, role)) => *role == Role::MapClose || matches!(token_type,
+                TokenType::Identifier | TokenType::Number | TokenType::String | TokenType::True | TokenType::False
+                | TokenType::Nil | TokenType::This | TokenType::RightParen | TokenType::RightBracket),
+            None => false,
+        }
+    }
+
+    fn needs_space(&self, token: &Token, role: Role) -> bool {
+        let Some((previous, previous_role)) = &self.previous else { return false };
+        if self.at_line_start
+            || matches!(previous, TokenType::LeftParen | TokenType::LeftBracket | TokenType::Dot | TokenType::Interpolation)
+            || matches!(previous_role, Role::Unary | Role::MapOpen)
+        {
+            return false;
+        }
+        match token.token_type {
+            TokenType::RightParen | TokenType::RightBracket | TokenType::Comma | TokenType::Semicolon
+                | TokenType::Dot | TokenType::Colon => false,
+            // The rest of an interpolated string starts with the `}` that ends the expression.
+            TokenType::String | TokenType::Interpolation if token.lexeme.starts_with('}') => false,
+            // A call or an index, as opposed to a grouping or a list literal.
+            TokenType::LeftParen | TokenType::LeftBracket => !self.follows_operand(),
+            _ => role != Role::MapClose && !(role == Role::BlockClose && *previous_role == Role::BlockOpen),
+        }
+    }
+
+    fn previous_role(&self) -> Option<Role> {
+        self.previous.as_ref().map(|(_, role)| *role)
+    }
+
+    fn newline(&mut self, blank: bool) {
+        self.out.push('\n');
+        if blank {
+            self.out.push('\n');
+        }
+        self.at_line_start = true;
+        self.pending_newline = false;
+        self.pending_space = false;
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.at_line_start {
+            self.out.push_str(&INDENT.repeat(self.indent));
+            self.at_line_start = false;
+        }
+        self.out.push_str(text);
+    }
+}
@@ -1,143 +1,156 @@
-use std::{rc::Rc, cell::RefCell};
+use std::{rc::Rc, cell::RefCell, cmp::Ordering, collections::HashMap};
+
+use crate::{expr::{Expr, ExprId}, literal::Literal, native, token::{Token, Span}, token_type::TokenType, error_handler::LoxError, stmt::Stmt, environment::{Environment}, rlox_function::RloxFunction, rlox_class::RloxClass, rlox_instance::RloxInstance, number::{ArithmeticOp, Number}};
+/// How deep Lox calls may nest before the interpreter reports a stack
+/// overflow. `main` gives the interpreter a stack big enough for this many.
+const MAX_CALL_DEPTH: usize = 10_000;
 
-use crate::{expr::Expr, literal::{Literal, Clock}, token::Token, token_type::TokenType, error_handler::error, stmt::Stmt, environment::{Environment}, rlox_function::RloxFunction};
 pub struct Interpreter {
     pub globals: Rc<RefCell<Environment>>,
-    pub environment: Rc<RefCell<Environment>>
+    pub environment: Rc<RefCell<Environment>>,
+    locals: HashMap<ExprId, usize>,
+    /// How many Lox calls are in progress.
+    call_depth: usize,
+}
+
+/// Why a statement stopped before running to completion. Each variant
+/// unwinds to a different place: `Return` to the enclosing call, `Break`
+/// and `Continue` to the innermost loop and `Error` all the way out.
+#[derive(Debug)]
+pub enum Unwind {
+    Return(Literal),
+    Break,
+    Continue,
+    Error(LoxError),
+}
+
+impl From<LoxError> for Unwind {
+    fn from(error: LoxError) -> Self {
+        Unwind::Error(error)
+    }
 }
 
 impl Interpreter {
 
     pub fn new() -> Self {
         let environment = Environment::new();
-        let interpreter = Interpreter { globals: environment.clone(), environment };
-        interpreter.globals.borrow_mut().define("clock".to_string(), Some(Literal::Callable(Rc::new(Clock{}))));
+        let interpreter = Interpreter { globals: environment.clone(), environment, locals: HashMap::new(), call_depth: 0 };
+        for native in native::natives() {
+            interpreter.globals.borrow_mut().define(native.name.to_string(), Some(Literal::Native(Rc::new(native))));
+        }
         interpreter
     }
 
-    pub fn interpret<'a>(&'a mut self, stmts: &'a [Stmt]) {
+    pub fn resolve(&mut self, id: ExprId, depth: usize) {
+        self.locals.insert(id, depth);
+    }
+
+    pub fn interpret<'a>(&'a mut self, stmts: &'a [Stmt]) -> Result<(), LoxError> {
         for stmt in stmts {
-            let _ = self.execute(stmt);
+            match self.execute(stmt) {
+                Ok(()) => (),
+                Err(Unwind::Error(error)) => return Err(error),
+                Err(unwind) => unreachable!("the parser and resolver keep {unwind:?} inside functions and loops"),
+            }
         }
+        Ok(())
     }
 
-    fn accept_statement<'a>(&'a mut self, stmt: &'a Stmt) -> Result<(), Option<Literal>> {
+    fn accept_statement<'a>(&'a mut self, stmt: &'a Stmt) -> Result<(), Unwind> {
         match stmt {
-            Stmt::Expr(expression) => { self.evaluate(expression); Ok(())},
+            Stmt::Expr(expression) => { self.evaluate(expression)?; Ok(())},
             Stmt::Print(expression) => {
-                if let Some(value) = self.evaluate(expression) {
-                    println!("{}", Literal::stringify(value));
-                }
+                let value = self.evaluate(expression)?;
+                println!("{}", Literal::stringify(value));
                 Ok(())
             },
             Stmt::Var(token, expression) => {
                 let mut value = None;
                 if let Some(expression) = expression {
-                    value = self.evaluate(expression);
+                    value = Some(self.evaluate(expression)?);
                 }
 
                 self.environment.borrow_mut().define(token.lexeme.clone(), value);
                 Ok(())
             },
-            Stmt::Block(statements) => {
+            Stmt::Block(statements, _) => {
                 self.execute_block(statements, Environment::from_existing(Rc::clone(&self.environment)))?;
                 Ok(())
             },
             Stmt::If(condition, then_branch, else_branch) => {
-                    if let Some(condition_result) = self.evaluate(condition){
-                        if self.is_truthy(&condition_result) {
-                            self.execute(then_branch)?
-                        }
-                        else if else_branch.is_some()  {
-                            self.execute(else_branch.as_ref().unwrap())?
-                        }
+                    let condition_result = self.evaluate(condition)?;
+                    if self.is_truthy(&condition_result) {
+                        self.execute(then_branch)?
                     }
-                    Ok(())
-            },
-            Stmt::While(condition, body) => {
-                if let Some(mut result) = self.evaluate(condition){
-                    while self.is_truthy(&result) {
-                        self.execute(body)?;
-                        result = self.evaluate(condition).unwrap();
+                    else if let Some(else_branch) = else_branch {
+                        self.execute(else_branch)?
                     }
-                }
-                Ok(())
+                    Ok(())
             },
+            Stmt::While(condition, body, increment) => self.handle_while(condition, body, increment.as_ref()),
+            Stmt::Break(_) => Err(Unwind::Break),
+            Stmt::Continue(_) => Err(Unwind::Continue),
             Stmt::Function(name, _, _) => {
-                let function = RloxFunction::new(stmt.clone(), Rc::clone(&self.environment));
-                self.environment.borrow_mut().define(name.lexeme.clone(), Some(Literal::Callable(Rc::new(function))));
+                self.handle_function(name, stmt);
                 Ok(())
             },
             Stmt::Return(_ , value) => {
-                let value = if let Some(value) = value {
-                    self.evaluate(value)
-                }
-                else {
-                    None
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Literal::Null,
                 };
-                Err(value)
-            }
+                Err(Unwind::Return(value))
+            },
+            Stmt::Class(name, superclass, methods) => self.handle_class(name, superclass.as_ref(), methods),
         }
     }
 
-    fn accept_expression<'a>(&'a mut self, expression: &'a Expr) -> Option<Literal>{
+    fn accept_expression<'a>(&'a mut self, expression: &'a Expr) -> Result<Literal, LoxError>{
         match expression{
             Expr::Binary(left, operator, right) => self.handle_binary(left.as_ref(), operator, right.as_ref()),
             Expr::Unary(operator, right) => self.handle_unary(operator, right.as_ref()),
-            Expr::Literal(literal) => Some(literal.clone()),
-            Expr::Grouping(value) => self.evaluate(value.as_ref()),
-            Expr::Variable(value) => self.environment.borrow().get(value),
-            Expr::Assign(name, value) => {
-                let value = self.evaluate((*value).as_ref());
-                self.environment.borrow_mut().assign(name, value.as_ref()?);
-                value
-            },
-            Expr::Logical(left, operator, right) => {
-                let left = self.evaluate(left);
-                if let TokenType::Or = operator.token_type {
-                    if self.is_truthy(left.as_ref()?) { return left; };
-                }
-                else if !self.is_truthy(left.as_ref()?) { return left };
+            Expr::Literal(literal, _) => Ok(literal.clone()),
+            Expr::Grouping(value, _) => self.evaluate(value.as_ref()),
+            Expr::Stringify(value) => Ok(Literal::String(Literal::stringify(self.evaluate(value)?))),
+            Expr::Variable(name, id) => self.look_up_variable(name, *id),
+            Expr::Assign(name, value, id) => self.handle_assign(name, value, *id),
+            Expr::Logical(left, operator, right) => self.handle_logical(left, operator, right),
+            Expr::Call(callee, paren, arguments) => self.handle_call(expression, callee, paren, arguments),
+            Expr::Get(object, name) => self.handle_get(object, name),
+            Expr::Set(object, name, value) => self.handle_set(object, name, value),
+            Expr::This(keyword, id) => self.look_up_variable(keyword, *id),
+            Expr::List(_, items, _) => self.handle_list(items),
+            Expr::Map(open, entries, _) => self.handle_map(expression, open, entries),
+            Expr::Index(object, bracket, index) => self.handle_index(expression, object, bracket, index),
+            Expr::IndexSet(object, bracket, index, value) => self.handle_index_set(expression, object, bracket, index, value),
+            Expr::Super(_, method, id) => self.handle_super(method, *id),
+        }
+    }
 
-                self.evaluate(right)
-            },
-            Expr::Call(callee, paren, arguments) => {
-                let callee = self.evaluate(callee)?;
- 
-                let mut args = vec![];
-                for argument in arguments {
-                    args.push(self.evaluate(argument.as_ref().unwrap()));
-                }
-                if let Literal::Callable(callee) = callee {
-                    if arguments.len() != callee.arity() {
-                        error::runtime_error(paren, format!("Expected {}  arguments, but got {}." , callee.arity(), arguments.len()).as_str())
-                    }
-                    callee.call(self, &args)
-                }
-                else {
-                    error::runtime_error(paren, "Can only call functions and classes.");
-                    None
-                }
-            },
+    fn look_up_variable(&self, name: &Token, id: ExprId) -> Result<Literal, LoxError> {
+        match self.locals.get(&id) {
+            Some(distance) => self.environment.borrow().get_at(*distance, &name.lexeme)
+                .ok_or_else(|| LoxError::runtime(name, &format!("Undefined variable '{}'.", name.lexeme))),
+            None => self.globals.borrow().get(name),
         }
     }
 
-    fn evaluate<'a>(&'a mut self, expr: &'a Expr) -> Option<Literal> {
+    pub fn evaluate<'a>(&'a mut self, expr: &'a Expr) -> Result<Literal, LoxError> {
         self.accept_expression(expr)
     }
 
-    fn execute(&mut self, stmt: &Stmt) -> Result<(), Option<Literal>> {
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         self.accept_statement(stmt)
     }
 
-    pub fn execute_block(&mut self, stmts: &Vec<Stmt>, environment: Rc<RefCell<Environment>>) -> Result<(), Option<Literal>> {
+    pub fn execute_block(&mut self, stmts: &Vec<Stmt>, environment: Rc<RefCell<Environment>>) -> Result<(), Unwind> {
         let previous = self.environment.clone();
         self.environment = environment;
 
         for statement in stmts {
-            if let Err(value) = self.execute(statement) {
+            if let Err(unwind) = self.execute(statement) {
                 self.environment = previous;
-                return Err(value);
+                return Err(unwind);
             }
         }
 
@@ -145,98 +158,244 @@ impl Interpreter {
         Ok(())
     }
 
-    fn handle_binary<'a>(&mut self, left: &'a Expr, operator: &Token, right: &'a Expr) -> Option<Literal>{
+    fn check_arity(&self, call: &Expr, paren: &Token, expected: usize, got: usize) -> Result<(), LoxError> {
+        if expected != got {
+            let message = format!("Expected {} arguments, but got {}." , expected, got);
+            return Err(LoxError::runtime(paren, &message).with_span(call.span()));
+        }
+        Ok(())
+    }
+
+    fn handle_binary<'a>(&mut self, left: &'a Expr, operator: &Token, right: &'a Expr) -> Result<Literal, LoxError>{
+        let span = left.span().merge(right.span());
         let left = self.evaluate(left)?;
         let right = self.evaluate(right)?;
- 
-        match (&left, &right) {
-            
-            (Literal::Float(left), Literal::Float(right)) => {
-                match &operator.token_type{
-                    TokenType::Plus => {
-                        Some(Literal::Float(left + right))
-                    }
-                    TokenType::Minus => {
-                        Some(Literal::Float(left - right))
-                    },
-                    TokenType::Slash => {
-                        Some(Literal::Float(left / right))
-                    },
-                    TokenType::Star => {
-                        Some(Literal::Float(left * right))
-                    }
-                    TokenType::Greater => {
-                        Some(Literal::Bool(left > right))
-                    }
-                    TokenType::GreaterEqual => {
-                        Some(Literal::Bool(left >= right))
-                    }
-                    TokenType::Less => {
-                        Some(Literal::Bool(left < right))
-                    }
-                    TokenType::LessEqual => {
-                        Some(Literal::Bool(left <= right))
-                    }
-                    TokenType::BangEqual => {
-                        Some(Literal::Bool(!self.is_equal(left, right)))
-                    }
-                    TokenType::EqualEqual => {
-                        Some(Literal::Bool(self.is_equal(left, right)))
-                    }
-                    _ =>  { error::runtime_error(operator, "Operands must be numbers"); None }
-                }
-            },
-            (Literal::String(left), Literal::String(right)) => {
-                match &operator.token_type{
-                    TokenType::Plus => {
-                        Some(Literal::String(left.to_owned() + right))
-                    }
-                    TokenType::BangEqual => {
-                        Some(Literal::Bool(!self.is_equal(left, right)))
-                    }
-                    TokenType::EqualEqual => {
-                        Some(Literal::Bool(self.is_equal(left, right)))
-                    }
-                    _ => { error::runtime_error(operator, "Operands must be two numbers or two strings"); None } 
-                }
+        self.binary_operation(operator, left, right, span)
+    }
+
+    /// Applies a binary operator to operands that are already evaluated.
+    /// Kept apart from `handle_binary`, which recurses, so its locals don't
+    /// take up room in every nested frame.
+    #[inline(never)]
+    fn binary_operation(&self, operator: &Token, left: Literal, right: Literal, span: Span) -> Result<Literal, LoxError> {
+        match operator.token_type {
+            TokenType::EqualEqual => return Ok(Literal::Bool(self.is_equal(&left, &right))),
+            TokenType::BangEqual => return Ok(Literal::Bool(!self.is_equal(&left, &right))),
+            _ => ()
+        }
+
+        if let (Literal::String(left), Literal::String(right), TokenType::Plus) = (&left, &right, &operator.token_type) {
+            return Ok(Literal::String(left.to_owned() + right));
+        }
+
+        let (Some(left), Some(right)) = (left.as_number(), right.as_number()) else {
+            return Err(LoxError::runtime(operator, "Operands must be two numbers or two strings").with_span(span));
+        };
+        let op = match operator.token_type {
+            TokenType::Greater => return Ok(Literal::Bool(Number::compare(left, right) == Some(Ordering::Greater))),
+            TokenType::GreaterEqual => return Ok(Literal::Bool(matches!(Number::compare(left, right), Some(Ordering::Greater | Ordering::Equal)))),
+            TokenType::Less => return Ok(Literal::Bool(Number::compare(left, right) == Some(Ordering::Less))),
+            TokenType::LessEqual => return Ok(Literal::Bool(matches!(Number::compare(left, right), Some(Ordering::Less | Ordering::Equal)))),
+            TokenType::Plus => ArithmeticOp::Add,
+            TokenType::Minus => ArithmeticOp::Subtract,
+            TokenType::Star => ArithmeticOp::Multiply,
+            TokenType::Slash => ArithmeticOp::Divide,
+            TokenType::TildeSlash => ArithmeticOp::FloorDivide,
+            TokenType::Percent => ArithmeticOp::Modulo,
+            _ => return Err(LoxError::runtime(operator, "Operands must be numbers").with_span(span)),
+        };
+        Number::arithmetic(op, left, right)
+            .map(Literal::from)
+            .map_err(|message| LoxError::runtime(operator, message).with_span(span))
+    }
+
+    fn handle_unary(&mut self, operator: &Token, expr: &Expr) -> Result<Literal, LoxError>{
+        let span = operator.span.merge(expr.span());
+        let right = self.evaluate(expr)?;
+        match (&operator.token_type, right.as_number()){
+            (TokenType::Minus, Some(number)) => {
+                number.negate().map(Literal::from).map_err(|message| LoxError::runtime(operator, message).with_span(span))
             }
-            (Literal::Bool(left), Literal::Bool(right)) => {
-                match operator.token_type{
-                    TokenType::BangEqual => {
-                        Some(Literal::Bool(!self.is_equal(left, right)))
-                    }
-                    TokenType::EqualEqual => {
-                        Some(Literal::Bool(self.is_equal(left, right)))
-                    }
-                    _ => unimplemented!()
-                }
+            (TokenType::Bang, _) => {
+                Ok(Literal::Bool(!self.is_truthy(&right)))
             }
-            (Literal::Null, Literal::Null) => {
-                match operator.token_type{
-                    TokenType::BangEqual => {
-                        Some(Literal::Bool(!self.is_equal(left, right)))
-                    }
-                    TokenType::EqualEqual => {
-                        Some(Literal::Bool(self.is_equal(left, right)))
-                    }
-                    _ => unimplemented!()
-                }
+            _ => Err(LoxError::runtime(operator, "Operand must be a number").with_span(span))
+        }
+    }
+
+    fn handle_while(&mut self, condition: &Expr, body: &Stmt, increment: Option<&Expr>) -> Result<(), Unwind> {
+        loop {
+            let result = self.evaluate(condition)?;
+            if !self.is_truthy(&result) {
+                break;
+            }
+            match self.execute(body) {
+                Ok(()) | Err(Unwind::Continue) => (),
+                Err(Unwind::Break) => break,
+                Err(unwind) => return Err(unwind),
+            }
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
             }
-            (_, _) => { error::runtime_error(operator, "Operands must be two numbers or two strings"); None }
         }
+        Ok(())
     }
 
-    fn handle_unary(&mut self, operator: &Token, expr: &Expr) -> Option<Literal>{
-        let right = self.evaluate(expr)?;
-        match (&operator.token_type, &right){
-            (TokenType::Minus, Literal::Float(value)) => {
-                Some(Literal::Float(-value))
+    #[inline(never)]
+    fn handle_function(&mut self, name: &Token, declaration: &Stmt) {
+        let function = RloxFunction::new(declaration.clone(), Rc::clone(&self.environment), false);
+        self.environment.borrow_mut().define(name.lexeme.clone(), Some(Literal::Callable(Rc::new(function))));
+    }
+
+    #[inline(never)]
+    fn handle_class(&mut self, name: &Token, superclass: Option<&Expr>, methods: &[Stmt]) -> Result<(), Unwind> {
+        let mut parent = None;
+        if let Some(superclass) = superclass {
+            match self.evaluate(superclass)? {
+                Literal::Class(class) => parent = Some(class),
+                _ => {
+                    let Expr::Variable(superclass_name, _) = superclass else {
+                        unreachable!("the parser only accepts a name as the superclass")
+                    };
+                    return Err(LoxError::runtime(superclass_name, "Superclass must be a class.").into());
+                },
             }
-            (TokenType::Bang, _) => {
-                Some(Literal::Bool(!self.is_truthy(&right)))
+        }
+
+        self.environment.borrow_mut().define(name.lexeme.clone(), None);
+
+        let enclosing = Rc::clone(&self.environment);
+        if let Some(parent) = &parent {
+            self.environment = Environment::from_existing(Rc::clone(&enclosing));
+            self.environment.borrow_mut().define("super".to_string(), Some(Literal::Class(Rc::clone(parent))));
+        }
+
+        let mut class_methods = HashMap::new();
+        for method in methods {
+            if let Stmt::Function(method_name, _, _) = method {
+                let is_initializer = method_name.lexeme == "init";
+                let function = RloxFunction::new(method.clone(), Rc::clone(&self.environment), is_initializer);
+                class_methods.insert(method_name.lexeme.clone(), Rc::new(function));
+            }
+        }
+
+        let class = RloxClass::new(name.lexeme.clone(), parent, class_methods);
+        self.environment = enclosing;
+        self.environment.borrow_mut().define(name.lexeme.clone(), Some(Literal::Class(Rc::new(class))));
+        Ok(())
+    }
+
+    fn handle_call(&mut self, call: &Expr, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Result<Literal, LoxError> {
+        let callee = self.evaluate(callee)?;
+
+        let mut args = vec![];
+        for argument in arguments {
+            args.push(self.evaluate(argument)?);
+        }
+
+        // Each Lox call nests several Rust calls, so deep recursion has to
+        // stop with a Lox error before it exhausts the native stack.
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(LoxError::runtime(paren, "Stack overflow.").with_span(call.span()));
+        }
+        self.call_depth += 1;
+        let result = match callee {
+            Literal::Callable(callee) => {
+                self.check_arity(call, paren, callee.arity(), args.len())
+                    .and_then(|()| callee.call(self, &args))
+            },
+            Literal::Native(native) => {
+                self.check_arity(call, paren, native.arity, args.len())
+                    .and_then(|()| (native.function)(&args).map_err(|message| LoxError::runtime(paren, &message).with_span(call.span())))
+            },
+            Literal::Class(class) => {
+                self.check_arity(call, paren, class.arity(), args.len())
+                    .and_then(|()| RloxClass::instantiate(&class, self, &args))
+            },
+            _ => Err(LoxError::runtime(paren, "Can only call functions and classes.").with_span(call.span()))
+        };
+        self.call_depth -= 1;
+        result
+    }
+
+    fn handle_assign(&mut self, name: &Token, value: &Expr, id: ExprId) -> Result<Literal, LoxError> {
+        let value = self.evaluate(value)?;
+        match self.locals.get(&id) {
+            Some(distance) => self.environment.borrow_mut().assign_at(*distance, name, &value),
+            None => self.globals.borrow_mut().assign(name, &value)?,
+        }
+        Ok(value)
+    }
+
+    fn handle_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<Literal, LoxError> {
+        let left = self.evaluate(left)?;
+        if let TokenType::Or = operator.token_type {
+            if self.is_truthy(&left) { return Ok(left); };
+        }
+        else if !self.is_truthy(&left) { return Ok(left) };
+
+        self.evaluate(right)
+    }
+
+    fn handle_get(&mut self, object: &Expr, name: &Token) -> Result<Literal, LoxError> {
+        if let Literal::Instance(instance) = self.evaluate(object)? {
+            return RloxInstance::get(&instance, name);
+        }
+        Err(LoxError::runtime(name, "Only instances have properties."))
+    }
+
+    fn handle_set(&mut self, object: &Expr, name: &Token, value: &Expr) -> Result<Literal, LoxError> {
+        if let Literal::Instance(instance) = self.evaluate(object)? {
+            let value = self.evaluate(value)?;
+            instance.borrow_mut().set(name, value.clone());
+            return Ok(value);
+        }
+        Err(LoxError::runtime(name, "Only instances have fields."))
+    }
+
+    fn handle_list(&mut self, items: &[Expr]) -> Result<Literal, LoxError> {
+        let mut values = vec![];
+        for item in items {
+            values.push(self.evaluate(item)?);
+        }
+        Ok(Literal::List(Rc::new(RefCell::new(values))))
+    }
+
+    fn handle_map(&mut self, map: &Expr, open: &Token, entries: &[(Expr, Expr)]) -> Result<Literal, LoxError> {
+        let mut pairs = vec![];
+        for (key, value) in entries {
+            pairs.push((self.evaluate(key)?, self.evaluate(value)?));
+        }
+        native::map_from_pairs(pairs).map_err(|message| LoxError::runtime(open, &message).with_span(map.span()))
+    }
+
+    fn handle_index(&mut self, index_expr: &Expr, object: &Expr, bracket: &Token, index: &Expr) -> Result<Literal, LoxError> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        native::index_get(&object, &index).map_err(|message| LoxError::runtime(bracket, &message).with_span(index_expr.span()))
+    }
+
+    fn handle_index_set(&mut self, index_expr: &Expr, object: &Expr, bracket: &Token, index: &Expr, value: &Expr) -> Result<Literal, LoxError> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        let value = self.evaluate(value)?;
+        native::index_set(&object, &index, value.clone())
+            .map_err(|message| LoxError::runtime(bracket, &message).with_span(index_expr.span()))?;
+        Ok(value)
+    }
+
+    #[inline(never)]
+    fn handle_super(&mut self, method: &Token, id: ExprId) -> Result<Literal, LoxError> {
+        let distance = self.locals[&id];
+        let superclass = self.environment.borrow().get_at(distance, "super");
+        let object = self.environment.borrow().get_at(distance - 1, "this");
+
+        if let (Some(Literal::Class(superclass)), Some(Literal::Instance(instance))) = (superclass, object) {
+            if let Some(method) = superclass.find_method(&method.lexeme) {
+                return Ok(Literal::Callable(Rc::new(method.bind(instance))));
             }
-            _ => { error::runtime_error(operator, "Operand must be a number"); None }
         }
+        Err(LoxError::runtime(method, &format!("Undefined property '{}'.", method.lexeme)))
     }
 
     fn is_truthy(&self, literal: &Literal) -> bool{
@@ -3,7 +3,9 @@ use crate::token_type::TokenType;
 
 pub static KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "and"    =>  TokenType::And,
+    "break"  =>  TokenType::Break,
     "class"  =>  TokenType::Class,
+    "continue" => TokenType::Continue,
     "false"  =>  TokenType::False,
     "for"    =>  TokenType::For,
     "fun"    =>  TokenType::Fun,
@@ -0,0 +1,106 @@
+use std::{env, path::PathBuf};
+
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter, history::DefaultHistory,
+    validate::Validator, Context, Editor, Helper,
+};
+
+use crate::keywords::KEYWORDS;
+
+/// What the user did at the prompt.
+pub enum Input {
+    Line(String),
+    /// Ctrl-C: drop whatever has been typed so far.
+    Interrupted,
+    /// Ctrl-D, or stdin ran out.
+    Eof,
+}
+
+/// The REPL's line editor: arrow-key editing, Ctrl-R reverse search, history
+/// kept in `~/.rlox_history` and tab completion of keywords and globals.
+pub struct LineEditor {
+    editor: Editor<Completions, DefaultHistory>,
+    history: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new() -> rustyline::Result<Self> {
+        let mut editor = Editor::new()?;
+        editor.set_helper(Some(Completions::default()));
+        let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(".rlox_history"));
+        if let Some(path) = &history {
+            // There is no history file yet on the first run.
+            let _ = editor.load_history(path);
+        }
+        Ok(LineEditor { editor, history })
+    }
+
+    /// Reads one line, completing `globals` as well as the keywords.
+    pub fn read_line(&mut self, prompt: &str, globals: Vec<String>) -> Input {
+        if let Some(completions) = self.editor.helper_mut() {
+            completions.globals = globals;
+        }
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = self.editor.add_history_entry(line.as_str());
+                }
+                Input::Line(line)
+            },
+            Err(ReadlineError::Interrupted) => Input::Interrupted,
+            Err(ReadlineError::Eof) => Input::Eof,
+            Err(err) => {
+                eprintln!("Error reading input: {err}");
+                Input::Eof
+            },
+        }
+    }
+
+    pub fn save_history(&mut self) {
+        if let Some(path) = &self.history {
+            if let Err(err) = self.editor.save_history(path) {
+                eprintln!("Error saving history to {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Completions {
+    globals: Vec<String>,
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    /// Completes the identifier that ends at the cursor.
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+            .last()
+            .map_or(pos, |(index, _)| index);
+        let prefix = &line[start..pos];
+
+        let mut candidates: Vec<String> = KEYWORDS
+            .keys()
+            .map(|keyword| keyword.to_string())
+            .chain(self.globals.iter().cloned())
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
@@ -1,48 +1,195 @@
-use std::{fmt::Debug, rc::Rc, time::SystemTime};
+use std::{fmt::Debug, rc::Rc, cell::RefCell};
 
-use crate::interpreter::Interpreter;
+use crate::{interpreter::Interpreter, native::{LoxMap, MapKey, Native, NativeValue}, number::Number, rlox_class::RloxClass, rlox_instance::RloxInstance, error_handler::LoxError};
 
 #[derive(Debug, Clone)]
 pub enum Literal {
     String(String),
-    Float(f32),
+    Int(i64),
+    Float(f64),
     Bool(bool),
     Null,
     Callable(Rc<dyn TCallable>),
+    Native(Rc<Native<Literal>>),
+    List(Rc<RefCell<Vec<Literal>>>),
+    Map(Rc<RefCell<LoxMap<Literal>>>),
+    Class(Rc<RloxClass>),
+    Instance(Rc<RefCell<RloxInstance>>),
 }
 
 pub trait TCallable: Debug {
     fn arity(&self) -> usize;
-    fn call(&self, interpreter: &mut Interpreter, arguments: &[Option<Literal>]) -> Option<Literal>;
+    fn call(&self, interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError>;
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Clock {}
-
-impl TCallable for Clock {
-    fn arity(&self) -> usize {
-        0
+impl PartialEq for Literal { 
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Literal::String(left), Literal::String(right)) => left == right,
+            (Literal::Bool(left), Literal::Bool(right)) => left == right,
+            (Literal::Null, Literal::Null) => true,
+            (Literal::Callable(left), Literal::Callable(right)) => Rc::ptr_eq(left, right),
+            (Literal::Native(left), Literal::Native(right)) => Rc::ptr_eq(left, right),
+            (Literal::List(left), Literal::List(right)) => Rc::ptr_eq(left, right),
+            (Literal::Map(left), Literal::Map(right)) => Rc::ptr_eq(left, right),
+            (Literal::Class(left), Literal::Class(right)) => Rc::ptr_eq(left, right),
+            (Literal::Instance(left), Literal::Instance(right)) => Rc::ptr_eq(left, right),
+            _ => match (self.as_number(), other.as_number()) {
+                (Some(left), Some(right)) => Number::equals(left, right),
+                _ => false,
+            },
+        }
     }
+}
 
-    fn call(&self, interpreter: &mut Interpreter, arguments: &[Option<Literal>]) -> Option<Literal> {
-       let time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as f32 / 1000.0;
-       Some(Literal::Float(time))
+impl Literal {
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Literal::Int(value) => Some(Number::Int(*value)),
+            Literal::Float(value) => Some(Number::Float(*value)),
+            _ => None,
+        }
     }
 
-}
-
-impl PartialEq for Literal { 
-    fn eq(&self, other: &Self) -> bool { self == other }
-}
+    /// Integral numbers print without a fractional part and everything else
+    /// with the shortest digits that parse back to the same `f64`. Very large
+    /// and very small magnitudes switch to exponent notation.
+    pub fn format_number(value: f64) -> String {
+        let magnitude = value.abs();
+        if value.is_finite() && (magnitude >= 1e21 || (magnitude != 0.0 && magnitude < 1e-7)) {
+            format!("{value:e}")
+        } else {
+            value.to_string()
+        }
+    }
 
-impl Literal {
     pub fn stringify(literal: Literal) -> String{
+        Literal::stringify_within(&literal, &mut Vec::new())
+    }
+
+    /// `open` holds the lists and maps being printed around this value, so
+    /// one that contains itself prints as `[...]` or `{...}` there instead
+    /// of recursing forever.
+    fn stringify_within(literal: &Literal, open: &mut Vec<*const ()>) -> String {
         match literal{
             Literal::Null => String::from("nil"),
-            Literal::String(value) => value,
-            Literal::Float(value) => value.to_string(),
+            Literal::String(value) => value.clone(),
+            Literal::Int(value) => value.to_string(),
+            Literal::Float(value) => Literal::format_number(*value),
             Literal::Bool(value) => value.to_string(),
-            Literal::Callable(_) => todo!(),
+            Literal::Callable(_) | Literal::Native(_) => String::from("<fn>"),
+            Literal::List(items) => {
+                let pointer = Rc::as_ptr(items) as *const ();
+                if open.contains(&pointer) {
+                    return String::from("[...]");
+                }
+                open.push(pointer);
+                let items: Vec<String> = items.borrow().iter().map(|item| Literal::stringify_within(item, open)).collect();
+                open.pop();
+                format!("[{}]", items.join(", "))
+            },
+            Literal::Map(map) => {
+                let pointer = Rc::as_ptr(map) as *const ();
+                if open.contains(&pointer) {
+                    return String::from("{...}");
+                }
+                open.push(pointer);
+                let entries: Vec<String> = map.borrow().iter()
+                    .map(|(key, value)| format!("{}: {}", Literal::stringify_within(&Literal::from_key(key), open), Literal::stringify_within(value, open)))
+                    .collect();
+                open.pop();
+                format!("{{{}}}", entries.join(", "))
+            },
+            Literal::Class(class) => class.name.clone(),
+            Literal::Instance(instance) => format!("{} instance", instance.borrow().class.name),
         }
     }
-}
\ No newline at end of file
+}
+
+impl From<Number> for Literal {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Int(value) => Literal::Int(value),
+            Number::Float(value) => Literal::Float(value),
+        }
+    }
+}
+
+impl NativeValue for Literal {
+    fn nil() -> Self {
+        Literal::Null
+    }
+
+    fn from_bool(value: bool) -> Self {
+        Literal::Bool(value)
+    }
+
+    fn from_int(value: i64) -> Self {
+        Literal::Int(value)
+    }
+
+    fn from_float(value: f64) -> Self {
+        Literal::Float(value)
+    }
+
+    fn from_string(value: &str) -> Self {
+        Literal::String(value.to_string())
+    }
+
+    fn from_list(items: Vec<Self>) -> Self {
+        Literal::List(Rc::new(RefCell::new(items)))
+    }
+
+    fn from_map(map: LoxMap<Self>) -> Self {
+        Literal::Map(Rc::new(RefCell::new(map)))
+    }
+
+    fn from_key(key: &MapKey) -> Self {
+        match key {
+            MapKey::Nil => Literal::Null,
+            MapKey::Bool(value) => Literal::Bool(*value),
+            MapKey::Int(value) => Literal::Int(*value),
+            MapKey::Float(bits) => Literal::Float(f64::from_bits(*bits)),
+            MapKey::String(value) => Literal::String(value.clone()),
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Literal::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Literal::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn as_list(&self) -> Option<&Rc<RefCell<Vec<Self>>>> {
+        match self {
+            Literal::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    fn as_map(&self) -> Option<&Rc<RefCell<LoxMap<Self>>>> {
+        match self {
+            Literal::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    fn to_key(&self) -> Option<MapKey> {
+        match self {
+            Literal::Null => Some(MapKey::Nil),
+            Literal::Bool(value) => Some(MapKey::Bool(*value)),
+            Literal::Int(value) => Some(MapKey::Int(*value)),
+            Literal::Float(value) => Some(MapKey::from_float(*value)),
+            Literal::String(value) => Some(MapKey::String(value.clone())),
+            _ => None,
+        }
+    }
+}
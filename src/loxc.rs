@@ -0,0 +1,437 @@
+//! The `.loxc` format for compiled programs.
+//!
+//! A file starts with the magic bytes `LOXC` and a little-endian `u16`
+//! format version, followed by the script function. A function is written as
+//! its name, arity, upvalue count, code, constant pool and span table; nested
+//! functions appear inline in the constant pool. All integers are
+//! little-endian `u32` unless noted otherwise.
+
+use std::rc::Rc;
+
+use crate::{chunk::{Chunk, OpCode}, token::Span, value::{Function, Value}};
+
+const MAGIC: &[u8; 4] = b"LOXC";
+const VERSION: u16 = 5;
+
+const TAG_NIL: u8 = 0;
+const TAG_BOOL: u8 = 1;
+const TAG_FLOAT: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_FUNCTION: u8 = 4;
+const TAG_INT: u8 = 5;
+
+pub fn is_loxc(path: &str) -> bool {
+    path.ends_with(".loxc")
+}
+
+pub fn serialize(function: &Function) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    write_function(&mut out, function);
+    out
+}
+
+pub fn deserialize(bytes: &[u8]) -> Result<Function, String> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(String::from("not a compiled Lox file"));
+    }
+    let version = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+    if version != VERSION {
+        return Err(format!("unsupported format version {version}, expected {VERSION}"));
+    }
+    let function = reader.function()?;
+    if reader.position != bytes.len() {
+        return Err(String::from("trailing data after the script"));
+    }
+    if function.arity != 0 || function.upvalue_count != 0 {
+        return Err(String::from("the script can't take parameters or capture variables"));
+    }
+    verify(&function)?;
+    Ok(function)
+}
+
+fn write_function(out: &mut Vec<u8>, function: &Function) {
+    write_string(out, &function.name);
+    write_u32(out, function.arity);
+    write_u32(out, function.upvalue_count);
+
+    let chunk = &function.chunk;
+    write_u32(out, chunk.code.len());
+    out.extend_from_slice(&chunk.code);
+
+    write_u32(out, chunk.constants.len());
+    for constant in &chunk.constants {
+        match constant {
+            Value::Nil => out.push(TAG_NIL),
+            Value::Bool(value) => {
+                out.push(TAG_BOOL);
+                out.push(*value as u8);
+            },
+            Value::Int(value) => {
+                out.push(TAG_INT);
+                out.extend_from_slice(&value.to_le_bytes());
+            },
+            Value::Float(value) => {
+                out.push(TAG_FLOAT);
+                out.extend_from_slice(&value.to_le_bytes());
+            },
+            Value::String(value) => {
+                out.push(TAG_STRING);
+                write_string(out, value);
+            },
+            Value::Function(function) => {
+                out.push(TAG_FUNCTION);
+                write_function(out, function);
+            },
+            _ => unreachable!("the compiler only emits primitive and function constants"),
+        }
+    }
+
+    write_u32(out, chunk.spans.len());
+    for (offset, span) in &chunk.spans {
+        for field in [*offset, span.start, span.end, span.line, span.column] {
+            write_u32(out, field);
+        }
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    let value = u32::try_from(value).expect("compiled programs are smaller than 4 GiB");
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len());
+    out.extend_from_slice(value.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn function(&mut self) -> Result<Function, String> {
+        let name = self.string()?;
+        let arity = self.u32()?;
+        let upvalue_count = self.u32()?;
+
+        let length = self.u32()?;
+        let code = self.take(length)?.to_vec();
+
+        let count = self.u32()?;
+        let mut constants = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            let constant = match self.u8()? {
+                TAG_NIL => Value::Nil,
+                TAG_BOOL => Value::Bool(self.u8()? != 0),
+                TAG_INT => Value::Int(i64::from_le_bytes(self.array()?)),
+                TAG_FLOAT => Value::Float(f64::from_le_bytes(self.array()?)),
+                TAG_STRING => Value::String(Rc::from(self.string()?)),
+                TAG_FUNCTION => Value::Function(Rc::new(self.function()?)),
+                tag => return Err(format!("unknown constant tag {tag}")),
+            };
+            constants.push(constant);
+        }
+
+        let count = self.u32()?;
+        let mut spans = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            let offset = self.u32()?;
+            let span = Span::new(self.u32()?, self.u32()?, self.u32()?, self.u32()?);
+            spans.push((offset, span));
+        }
+
+        Ok(Function { name, arity, upvalue_count, chunk: Chunk { code, constants, spans } })
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length = self.u32()?;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| String::from("invalid UTF-8 in string constant"))
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if length > self.remaining() {
+            return Err(String::from("unexpected end of file"));
+        }
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+}
+
+/// One decoded instruction, with the operands the verifier needs.
+struct Instruction {
+    op: OpCode,
+    length: usize,
+    /// The slot of a local access, the argument count of a call or the
+    /// element count of a list or map literal.
+    count: usize,
+    /// The local slots a closure captures.
+    captures: Vec<usize>,
+    /// Where a jump or loop goes.
+    target: Option<usize>,
+}
+
+impl Instruction {
+    /// How many values the instruction needs on the stack and how many it
+    /// leaves in their place.
+    fn stack_effect(&self) -> (usize, usize) {
+        match self.op {
+            OpCode::Constant | OpCode::Nil | OpCode::True | OpCode::False | OpCode::GetLocal | OpCode::GetGlobal
+                | OpCode::GetUpvalue | OpCode::Closure | OpCode::Class => (0, 1),
+            OpCode::Jump | OpCode::Loop => (0, 0),
+            OpCode::Pop | OpCode::DefineGlobal | OpCode::Print | OpCode::CloseUpvalue | OpCode::Return => (1, 0),
+            OpCode::SetLocal | OpCode::SetGlobal | OpCode::SetUpvalue | OpCode::GetProperty | OpCode::Not
+                | OpCode::Negate | OpCode::JumpIfFalse | OpCode::Stringify => (1, 1),
+            OpCode::SetProperty | OpCode::GetSuper | OpCode::Equal | OpCode::Greater | OpCode::GreaterEqual
+                | OpCode::Less | OpCode::LessEqual | OpCode::Add | OpCode::Subtract | OpCode::Multiply
+                | OpCode::Divide | OpCode::FloorDivide | OpCode::Modulo | OpCode::GetIndex => (2, 1),
+            OpCode::Inherit | OpCode::Method => (2, 1),
+            OpCode::SetIndex => (3, 1),
+            OpCode::Call | OpCode::Invoke => (self.count + 1, 1),
+            OpCode::SuperInvoke => (self.count + 2, 1),
+            OpCode::BuildList => (self.count, 1),
+            OpCode::BuildMap => (2 * self.count, 1),
+        }
+    }
+}
+
+/// Checks that a function read from a file can't make the VM misbehave:
+/// every opcode exists, every operand is in range, names are strings, jumps
+/// land on instructions, and no path through the code pops more values than
+/// it pushed or reads a local slot that isn't on the stack. The compiler
+/// only produces code like this, but a `.loxc` file may have been damaged.
+fn verify(function: &Function) -> Result<(), String> {
+    let fail = |offset: usize, message: &str| format!("invalid bytecode in '{}' at offset {offset}: {message}", function.name);
+    let code = &function.chunk.code;
+
+    let mut instructions: Vec<Option<Instruction>> = (0..code.len()).map(|_| None).collect();
+    let mut offset = 0;
+    while offset < code.len() {
+        let instruction = decode(function, offset).map_err(|message| fail(offset, &message))?;
+        let length = instruction.length;
+        instructions[offset] = Some(instruction);
+        offset += length;
+    }
+
+    // Follow every path from the entry, where the callee and its arguments
+    // are on the stack, tracking how many values each instruction sees.
+    let mut heights: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending = vec![(0, 1 + function.arity)];
+    while let Some((offset, height)) = pending.pop() {
+        let Some(instruction) = instructions.get(offset).and_then(Option::as_ref) else {
+            let message = if offset >= code.len() { "runs past the end of the code" } else { "jumps into the middle of an instruction" };
+            return Err(fail(offset, message));
+        };
+        match heights[offset] {
+            Some(known) if known == height => continue,
+            Some(_) => return Err(fail(offset, "reached with different stack heights")),
+            None => heights[offset] = Some(height),
+        }
+
+        let (pops, pushes) = instruction.stack_effect();
+        if pops > height {
+            return Err(fail(offset, "pops more values than the stack holds"));
+        }
+        let reads_local = matches!(instruction.op, OpCode::GetLocal | OpCode::SetLocal) && instruction.count >= height;
+        if reads_local || instruction.captures.iter().any(|slot| *slot >= height) {
+            return Err(fail(offset, "local slot out of range"));
+        }
+
+        let height = height - pops + pushes;
+        match instruction.op {
+            OpCode::Return => (),
+            OpCode::Jump | OpCode::Loop => pending.extend(instruction.target.map(|target| (target, height))),
+            _ => {
+                pending.push((offset + instruction.length, height));
+                pending.extend(instruction.target.map(|target| (target, height)));
+            },
+        }
+    }
+
+    for constant in &function.chunk.constants {
+        if let Value::Function(nested) = constant {
+            verify(nested)?;
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the instruction at `offset`, checking the operands that don't
+/// depend on the state of the stack.
+fn decode(function: &Function, offset: usize) -> Result<Instruction, String> {
+    let chunk = &function.chunk;
+    let byte = |index: usize| chunk.code.get(offset + index).copied().ok_or(String::from("instruction is cut off"));
+    let short = |index: usize| Ok::<usize, String>(u16::from_be_bytes([byte(index)?, byte(index + 1)?]) as usize);
+    let constant = |index: usize| chunk.constants.get(short(index)?).ok_or(String::from("constant index out of range"));
+    let name = |index: usize| match constant(index)? {
+        Value::String(_) => Ok(()),
+        _ => Err(String::from("name operand is not a string")),
+    };
+
+    let op = OpCode::from_byte(byte(0)?).ok_or(format!("unknown opcode {}", byte(0)?))?;
+    let mut instruction = Instruction { op, length: 1, count: 0, captures: Vec::new(), target: None };
+    match op {
+        OpCode::Constant => {
+            constant(1)?;
+            instruction.length = 3;
+        },
+        OpCode::GetGlobal | OpCode::DefineGlobal | OpCode::SetGlobal | OpCode::GetProperty | OpCode::SetProperty
+            | OpCode::GetSuper | OpCode::Class | OpCode::Method => {
+            name(1)?;
+            instruction.length = 3;
+        },
+        OpCode::GetLocal | OpCode::SetLocal | OpCode::Call => {
+            instruction.count = byte(1)? as usize;
+            instruction.length = 2;
+        },
+        OpCode::GetUpvalue | OpCode::SetUpvalue => {
+            if byte(1)? as usize >= function.upvalue_count {
+                return Err(String::from("upvalue index out of range"));
+            }
+            instruction.length = 2;
+        },
+        OpCode::BuildList | OpCode::BuildMap => {
+            instruction.count = short(1)?;
+            instruction.length = 3;
+        },
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = short(1)?;
+            let target = if op == OpCode::Loop { (offset + 3).checked_sub(jump) } else { Some(offset + 3 + jump) };
+            instruction.target = Some(target.filter(|target| *target < chunk.code.len()).ok_or(String::from("jump target outside the code"))?);
+            instruction.length = 3;
+        },
+        OpCode::Invoke | OpCode::SuperInvoke => {
+            name(1)?;
+            instruction.count = byte(3)? as usize;
+            instruction.length = 4;
+        },
+        OpCode::Closure => {
+            let Value::Function(nested) = constant(1)? else {
+                return Err(String::from("closure of a constant that isn't a function"));
+            };
+            instruction.length = 3;
+            for _ in 0..nested.upvalue_count {
+                let (is_local, index) = (byte(instruction.length)?, byte(instruction.length + 1)? as usize);
+                match is_local {
+                    1 => instruction.captures.push(index),
+                    0 if index < function.upvalue_count => (),
+                    0 => return Err(String::from("upvalue index out of range")),
+                    _ => return Err(String::from("invalid upvalue kind")),
+                }
+                instruction.length += 2;
+            }
+        },
+        _ => (),
+    }
+    Ok(instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compiler::Compiler, parser::Parser, scanner::Scanner};
+
+    fn compile(source: &str) -> Rc<Function> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens().expect("the source scans").clone();
+        let statements = Parser::new(tokens).parse().expect("the source parses");
+        Compiler::new().compile(&statements).expect("the source compiles")
+    }
+
+    /// A script made of `code`, with `constants` in its pool.
+    fn script(code: &[u8], constants: Vec<Value>) -> Vec<u8> {
+        let mut function = Function { name: String::from("script"), ..Function::default() };
+        for byte in code {
+            function.chunk.write(*byte, Span::default());
+        }
+        for constant in constants {
+            function.chunk.add_constant(constant);
+        }
+        serialize(&function)
+    }
+
+    #[test]
+    fn round_trip_keeps_everything() {
+        let source = r#"
+            class A { init(x) { this.x = x; } get() { return this.x; } }
+            class B < A { get() { return super.get() ~/ 2; } }
+            fun counter() { var n = 0; fun next() { n = n + 1; return n; } return next; }
+            var values = [nil, true, false, 1, -2.5, "text", {"key": B(7).get()}];
+            for (var i = 0; i < 3; i = i + 1) { if (i == 1) continue; print "${i}: ${values[i]}"; }
+        "#;
+        let function = compile(source);
+        let bytes = serialize(&function);
+        let loaded = deserialize(&bytes).expect("the file loads");
+
+        assert_eq!(loaded.chunk.code, function.chunk.code);
+        assert_eq!(loaded.chunk.spans, function.chunk.spans);
+        assert_eq!(loaded.chunk.constants.len(), function.chunk.constants.len());
+        assert_eq!(serialize(&loaded), bytes);
+    }
+
+    #[test]
+    fn rejects_damaged_headers_and_truncation() {
+        let bytes = serialize(&compile("print 1;"));
+        assert!(deserialize(&bytes[..bytes.len() - 1]).is_err());
+        assert!(deserialize(&[bytes.as_slice(), &[0]].concat()).is_err());
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(deserialize(&wrong_magic).is_err());
+
+        let mut wrong_version = bytes;
+        wrong_version[4] ^= 0xff;
+        assert!(deserialize(&wrong_version).is_err());
+    }
+
+    #[test]
+    fn accepts_minimal_script() {
+        let nil = OpCode::Nil as u8;
+        let ret = OpCode::Return as u8;
+        assert!(deserialize(&script(&[nil, ret], vec![])).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_bytecode() {
+        let op = |op: OpCode| op as u8;
+        let name = || vec![Value::String(Rc::from("x"))];
+        let cases: Vec<(&str, Vec<u8>, Vec<Value>)> = vec![
+            ("unknown opcode", vec![255], vec![]),
+            ("constant out of range", vec![op(OpCode::Constant), 0, 1, op(OpCode::Return)], name()),
+            ("name that isn't a string", vec![op(OpCode::GetGlobal), 0, 0, op(OpCode::Return)], vec![Value::Int(1)]),
+            ("local out of range", vec![op(OpCode::GetLocal), 1, op(OpCode::Return)], vec![]),
+            ("upvalue out of range", vec![op(OpCode::GetUpvalue), 0, op(OpCode::Return)], vec![]),
+            ("jump past the end", vec![op(OpCode::Jump), 0, 9, op(OpCode::Nil), op(OpCode::Return)], vec![]),
+            ("loop before the start", vec![op(OpCode::Loop), 0, 9, op(OpCode::Nil), op(OpCode::Return)], vec![]),
+            ("jump into an operand", vec![op(OpCode::Jump), 0, 1, op(OpCode::Constant), 0, 0, op(OpCode::Return)], name()),
+            ("stack underflow", vec![op(OpCode::Pop), op(OpCode::Pop), op(OpCode::Nil), op(OpCode::Return)], vec![]),
+            ("cut-off operand", vec![op(OpCode::Nil), op(OpCode::Constant), 0], name()),
+            ("running off the end", vec![op(OpCode::Nil)], vec![]),
+        ];
+        for (description, code, constants) in cases {
+            assert!(deserialize(&script(&code, constants)).is_err(), "{description} was accepted");
+        }
+    }
+}
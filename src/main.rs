@@ -1,5 +1,6 @@
-use code_runner::CodeRunner;
-use std::env;
+use cli::Command;
+use code_runner::{CodeRunner, FormatOutcome};
+use std::{env, process, thread};
 mod code_runner;
 mod expr;
 mod interpreter;
@@ -13,16 +14,83 @@ mod error_handler;
 mod stmt;
 mod environment;
 mod rlox_function;
+mod rlox_class;
+mod rlox_instance;
+mod resolver;
+mod diagnostic;
+mod value;
+mod chunk;
+mod compiler;
+mod vm;
+mod disassembler;
+mod loxc;
+mod number;
+mod native;
+mod line_editor;
+mod ast_printer;
+mod cli;
+mod formatter;
+mod test_runner;
+
+/// The tree-walker recurses in Rust for every Lox call, so rlox runs on a
+/// thread with a much bigger stack than the main thread's 8 MiB. That leaves
+/// room for the interpreter's call-depth limit even in a debug build, where
+/// frames are several times larger. Only the pages actually used are mapped.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
-    let args = env::args().collect::<Vec<String>>();
+    let runner = thread::Builder::new().name(String::from("rlox")).stack_size(STACK_SIZE).spawn(run);
+    let result = runner.unwrap_or_else(|err| {
+        eprintln!("Error starting rlox: {err}");
+        process::exit(70)
+    }).join();
+    if result.is_err() {
+        // The panic message has already been printed.
+        process::exit(101)
+    }
+}
+
+fn run() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let options = cli::parse(&args).unwrap_or_else(|message| {
+        eprintln!("rlox: {message}");
+        eprintln!("Run 'rlox --help' for usage.");
+        process::exit(64)
+    });
+
     let mut code_runner = CodeRunner::new();
+    code_runner.set_backend(options.backend);
+    code_runner.set_dump_bytecode(options.dump_bytecode);
+    code_runner.set_warnings_as_errors(options.warnings_as_errors);
+    code_runner.set_color(options.color.enabled());
 
-    if args.len() >= 3 {
-        println!("Usage: rlox [script]");
-    } else if args.len() == 2 {
-        code_runner.run_file(args[1].to_owned());
-    } else {
-        code_runner.run_prompt();
+    let child_flags = options.child_flags();
+    match options.command {
+        Command::Run { path, args } => {
+            native::set_script_args(args);
+            code_runner.run_file(path);
+        },
+        Command::Repl => code_runner.run_prompt(),
+        Command::Check(path) => code_runner.check_file(path),
+        Command::Fmt { paths, check } => {
+            // Format every file, even after one that needed it or failed.
+            let outcomes: Vec<FormatOutcome> = paths.into_iter().map(|path| code_runner.format_file(path, check)).collect();
+            if outcomes.contains(&FormatOutcome::Invalid) {
+                process::exit(65)
+            }
+            if outcomes.contains(&FormatOutcome::Failed) || (check && outcomes.contains(&FormatOutcome::Reformatted)) {
+                process::exit(1)
+            }
+        },
+        Command::Tokens(path) => code_runner.print_file_tokens(path),
+        Command::Ast(path) => code_runner.print_file_ast(path),
+        Command::Test(paths) => {
+            if !test_runner::run_tests(&paths, &child_flags) {
+                process::exit(1)
+            }
+        },
+        Command::Compile { path, output } => code_runner.compile_file(path, output),
+        Command::Help => print!("{}", cli::USAGE),
+        Command::Version => println!("rlox {}", env!("CARGO_PKG_VERSION")),
     }
 }
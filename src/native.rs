@@ -0,0 +1,279 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc, sync::OnceLock, time::SystemTime};
+
+use crate::literal::Literal;
+
+/// What the built-in functions need to know about a runtime value. Both the
+/// tree-walker's `Literal` and the VM's `Value` implement it, so every native
+/// and the indexing rules are written once and behave the same on both
+/// backends.
+pub trait NativeValue: Clone + Sized {
+    fn nil() -> Self;
+    fn from_bool(value: bool) -> Self;
+    fn from_int(value: i64) -> Self;
+    fn from_float(value: f64) -> Self;
+    fn from_string(value: &str) -> Self;
+    fn from_list(items: Vec<Self>) -> Self;
+    fn from_map(map: LoxMap<Self>) -> Self;
+    fn from_key(key: &MapKey) -> Self;
+    fn as_int(&self) -> Option<i64>;
+    fn as_str(&self) -> Option<&str>;
+    fn as_list(&self) -> Option<&Rc<RefCell<Vec<Self>>>>;
+    fn as_map(&self) -> Option<&Rc<RefCell<LoxMap<Self>>>>;
+    /// The map key for this value, if it is one of the hashable types.
+    fn to_key(&self) -> Option<MapKey>;
+}
+
+/// The hashable subset of values that can be used as map keys. Floats with
+/// an integral value are stored as ints, so `m[1]` and `m[1.0]` agree with
+/// `1 == 1.0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(u64),
+    String(String),
+}
+
+impl MapKey {
+    pub fn from_float(value: f64) -> MapKey {
+        if value.fract() == 0.0 && value >= i64::MIN as f64 && value < i64::MAX as f64 {
+            MapKey::Int(value as i64)
+        } else {
+            MapKey::Float(value.to_bits())
+        }
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapKey::Nil => write!(f, "nil"),
+            MapKey::Bool(value) => write!(f, "{value}"),
+            MapKey::Int(value) => write!(f, "{value}"),
+            MapKey::Float(bits) => write!(f, "{}", Literal::format_number(f64::from_bits(*bits))),
+            MapKey::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// A map that remembers insertion order, so printing a map and `keys()`
+/// are deterministic.
+#[derive(Debug)]
+pub struct LoxMap<V> {
+    entries: Vec<(MapKey, V)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl<V> LoxMap<V> {
+    pub fn new() -> Self {
+        LoxMap { entries: Vec::new(), positions: HashMap::new() }
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&V> {
+        self.positions.get(key).map(|position| &self.entries[*position].1)
+    }
+
+    pub fn insert(&mut self, key: MapKey, value: V) {
+        match self.positions.get(&key) {
+            Some(position) => self.entries[*position].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            },
+        }
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<V> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (key, _) in &self.entries[position..] {
+            *self.positions.get_mut(key).expect("every entry is indexed") -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&MapKey, &V)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+/// The command-line arguments after the script path, returned by `args()`.
+static SCRIPT_ARGS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Sets what `args()` returns. Only the first call has an effect.
+pub fn set_script_args(args: Vec<String>) {
+    let _ = SCRIPT_ARGS.set(args);
+}
+
+pub type NativeFn<V> = fn(&[V]) -> Result<V, String>;
+
+pub struct Native<V> {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: NativeFn<V>,
+}
+
+impl<V> fmt::Debug for Native<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// Every built-in function, to be defined as a global by each backend.
+pub fn natives<V: NativeValue>() -> Vec<Native<V>> {
+    vec![
+        Native { name: "clock", arity: 0, function: clock },
+        Native { name: "len", arity: 1, function: len },
+        Native { name: "push", arity: 2, function: push },
+        Native { name: "pop", arity: 1, function: pop },
+        Native { name: "insert", arity: 3, function: insert },
+        Native { name: "remove", arity: 2, function: remove },
+        Native { name: "slice", arity: 3, function: slice },
+        Native { name: "keys", arity: 1, function: keys },
+        Native { name: "values", arity: 1, function: values },
+        Native { name: "has", arity: 2, function: has },
+        Native { name: "delete", arity: 2, function: delete },
+        Native { name: "args", arity: 0, function: args },
+    ]
+}
+
+/// Builds a map literal from its evaluated key/value pairs.
+pub fn map_from_pairs<V: NativeValue>(pairs: Vec<(V, V)>) -> Result<V, String> {
+    let mut map = LoxMap::new();
+    for (key, value) in pairs {
+        map.insert(map_key(&key)?, value);
+    }
+    Ok(V::from_map(map))
+}
+
+/// `target[index]`.
+pub fn index_get<V: NativeValue>(target: &V, index: &V) -> Result<V, String> {
+    if let Some(map) = target.as_map() {
+        let key = map_key(index)?;
+        return map.borrow().get(&key).cloned().ok_or_else(|| format!("Undefined key '{key}'."));
+    }
+    let list = target.as_list().ok_or("Only lists and maps can be indexed.")?;
+    let list = list.borrow();
+    let position = position(index, list.len())?;
+    Ok(list[position].clone())
+}
+
+/// `target[index] = value`.
+pub fn index_set<V: NativeValue>(target: &V, index: &V, value: V) -> Result<(), String> {
+    if let Some(map) = target.as_map() {
+        map.borrow_mut().insert(map_key(index)?, value);
+        return Ok(());
+    }
+    let list = target.as_list().ok_or("Only lists and maps can be indexed.")?;
+    let mut list = list.borrow_mut();
+    let position = position(index, list.len())?;
+    list[position] = value;
+    Ok(())
+}
+
+fn map_key<V: NativeValue>(key: &V) -> Result<MapKey, String> {
+    key.to_key().ok_or_else(|| String::from("Map keys must be strings, numbers, booleans or nil."))
+}
+
+fn position<V: NativeValue>(index: &V, len: usize) -> Result<usize, String> {
+    let index = index.as_int().ok_or("List index must be an integer.")?;
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index < len)
+        .ok_or_else(|| format!("Index {index} out of bounds for list of length {len}."))
+}
+
+fn list_argument<'a, V: NativeValue>(arguments: &'a [V], name: &str) -> Result<&'a Rc<RefCell<Vec<V>>>, String> {
+    arguments[0].as_list().ok_or_else(|| format!("{name}() expects a list as its first argument."))
+}
+
+fn clock<V: NativeValue>(_arguments: &[V]) -> Result<V, String> {
+    let elapsed = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_err(|err| err.to_string())?;
+    Ok(V::from_float(elapsed.as_secs_f64()))
+}
+
+fn map_argument<'a, V: NativeValue>(arguments: &'a [V], name: &str) -> Result<&'a Rc<RefCell<LoxMap<V>>>, String> {
+    arguments[0].as_map().ok_or_else(|| format!("{name}() expects a map as its first argument."))
+}
+
+fn len<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    let argument = &arguments[0];
+    let len = if let Some(list) = argument.as_list() {
+        list.borrow().len()
+    } else if let Some(map) = argument.as_map() {
+        map.borrow().len()
+    } else if let Some(string) = argument.as_str() {
+        string.chars().count()
+    } else {
+        return Err(String::from("len() expects a list, map or string."));
+    };
+    Ok(V::from_int(len as i64))
+}
+
+fn push<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    list_argument(arguments, "push")?.borrow_mut().push(arguments[1].clone());
+    Ok(V::nil())
+}
+
+fn pop<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    list_argument(arguments, "pop")?.borrow_mut().pop().ok_or_else(|| String::from("pop() from an empty list."))
+}
+
+fn insert<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    let mut list = list_argument(arguments, "insert")?.borrow_mut();
+    // Inserting at the end is allowed, so the bound is one past the last index.
+    let position = position(&arguments[1], list.len() + 1)?;
+    list.insert(position, arguments[2].clone());
+    Ok(V::nil())
+}
+
+fn remove<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    let mut list = list_argument(arguments, "remove")?.borrow_mut();
+    let position = position(&arguments[1], list.len())?;
+    Ok(list.remove(position))
+}
+
+/// `slice(list, start, end)` copies the elements from `start` up to but not
+/// including `end` into a new list.
+fn slice<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    let list = list_argument(arguments, "slice")?.borrow();
+    let (Some(start), Some(end)) = (arguments[1].as_int(), arguments[2].as_int()) else {
+        return Err(String::from("slice() bounds must be integers."));
+    };
+    match (usize::try_from(start), usize::try_from(end)) {
+        (Ok(start), Ok(end)) if start <= end && end <= list.len() => Ok(V::from_list(list[start..end].to_vec())),
+        _ => Err(format!("Slice {start}..{end} out of bounds for list of length {}.", list.len())),
+    }
+}
+
+fn keys<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    let map = map_argument(arguments, "keys")?.borrow();
+    Ok(V::from_list(map.iter().map(|(key, _)| V::from_key(key)).collect()))
+}
+
+fn values<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    let map = map_argument(arguments, "values")?.borrow();
+    Ok(V::from_list(map.iter().map(|(_, value)| value.clone()).collect()))
+}
+
+fn has<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    let map = map_argument(arguments, "has")?.borrow();
+    Ok(V::from_bool(map.get(&map_key(&arguments[1])?).is_some()))
+}
+
+/// Removes `key` and returns its value, or nil if it was not present.
+fn delete<V: NativeValue>(arguments: &[V]) -> Result<V, String> {
+    let mut map = map_argument(arguments, "delete")?.borrow_mut();
+    Ok(map.remove(&map_key(&arguments[1])?).unwrap_or_else(V::nil))
+}
+
+/// The script's command-line arguments as a new list of strings.
+fn args<V: NativeValue>(_arguments: &[V]) -> Result<V, String> {
+    let args = SCRIPT_ARGS.get().map_or(&[][..], Vec::as_slice);
+    Ok(V::from_list(args.iter().map(|arg| V::from_string(arg)).collect()))
+}
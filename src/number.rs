@@ -0,0 +1,145 @@
+use std::cmp::Ordering;
+
+/// A Lox number as seen by arithmetic. Both backends convert their operands
+/// to `Number` so ints and floats follow the same rules everywhere: two ints
+/// stay an int (with overflow checks), anything involving a float is a float.
+#[derive(Debug, Clone, Copy)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    FloorDivide,
+    Modulo,
+}
+
+impl Number {
+    pub fn as_float(self) -> f64 {
+        match self {
+            Number::Int(value) => value as f64,
+            Number::Float(value) => value,
+        }
+    }
+
+    /// `/` always produces a float; `~/` and `%` floor towards negative
+    /// infinity, so `a == b * (a ~/ b) + a % b` holds for ints and floats.
+    pub fn arithmetic(op: ArithmeticOp, left: Number, right: Number) -> Result<Number, &'static str> {
+        match (left, right) {
+            (Number::Int(left), Number::Int(right)) if op != ArithmeticOp::Divide => {
+                Number::int_arithmetic(op, left, right).map(Number::Int)
+            },
+            _ => Ok(Number::Float(Number::float_arithmetic(op, left.as_float(), right.as_float()))),
+        }
+    }
+
+    pub fn negate(self) -> Result<Number, &'static str> {
+        match self {
+            Number::Int(value) => value.checked_neg().map(Number::Int).ok_or("Integer overflow."),
+            Number::Float(value) => Ok(Number::Float(-value)),
+        }
+    }
+
+    pub fn compare(left: Number, right: Number) -> Option<Ordering> {
+        match (left, right) {
+            (Number::Int(left), Number::Int(right)) => Some(left.cmp(&right)),
+            _ => left.as_float().partial_cmp(&right.as_float()),
+        }
+    }
+
+    pub fn equals(left: Number, right: Number) -> bool {
+        Number::compare(left, right) == Some(Ordering::Equal)
+    }
+
+    fn int_arithmetic(op: ArithmeticOp, left: i64, right: i64) -> Result<i64, &'static str> {
+        if matches!(op, ArithmeticOp::FloorDivide | ArithmeticOp::Modulo) && right == 0 {
+            return Err("Division by zero.");
+        }
+        let result = match op {
+            ArithmeticOp::Add => left.checked_add(right),
+            ArithmeticOp::Subtract => left.checked_sub(right),
+            ArithmeticOp::Multiply => left.checked_mul(right),
+            ArithmeticOp::FloorDivide => left.checked_div(right).map(|quotient| {
+                if left % right != 0 && (left < 0) != (right < 0) { quotient - 1 } else { quotient }
+            }),
+            ArithmeticOp::Modulo => {
+                // Only the quotient of `i64::MIN % -1` overflows; the remainder is 0.
+                let remainder = left.wrapping_rem(right);
+                Some(if remainder != 0 && (remainder < 0) != (right < 0) { remainder + right } else { remainder })
+            },
+            ArithmeticOp::Divide => unreachable!("'/' is always float division"),
+        };
+        result.ok_or("Integer overflow.")
+    }
+
+    fn float_arithmetic(op: ArithmeticOp, left: f64, right: f64) -> f64 {
+        match op {
+            ArithmeticOp::Add => left + right,
+            ArithmeticOp::Subtract => left - right,
+            ArithmeticOp::Multiply => left * right,
+            ArithmeticOp::Divide => left / right,
+            ArithmeticOp::FloorDivide => (left / right).floor(),
+            ArithmeticOp::Modulo => left - right * (left / right).floor(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(op: ArithmeticOp, left: i64, right: i64) -> Result<i64, &'static str> {
+        match Number::arithmetic(op, Number::Int(left), Number::Int(right))? {
+            Number::Int(value) => Ok(value),
+            Number::Float(value) => panic!("two ints gave the float {value}"),
+        }
+    }
+
+    fn float(op: ArithmeticOp, left: f64, right: f64) -> f64 {
+        Number::arithmetic(op, Number::Float(left), Number::Float(right)).unwrap().as_float()
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert_eq!(int(ArithmeticOp::Add, i64::MAX, 1), Err("Integer overflow."));
+        assert_eq!(int(ArithmeticOp::Subtract, i64::MIN, 1), Err("Integer overflow."));
+        assert_eq!(int(ArithmeticOp::Multiply, i64::MIN, -1), Err("Integer overflow."));
+        assert_eq!(int(ArithmeticOp::FloorDivide, i64::MIN, -1), Err("Integer overflow."));
+        assert_eq!(Number::Int(i64::MIN).negate().map(Number::as_float), Err("Integer overflow."));
+    }
+
+    #[test]
+    fn remainder_of_min_by_minus_one_is_zero() {
+        assert_eq!(int(ArithmeticOp::Modulo, i64::MIN, -1), Ok(0));
+    }
+
+    #[test]
+    fn floor_division_and_modulo_round_down() {
+        for (left, right, quotient, remainder) in [(7, 2, 3, 1), (-7, 2, -4, 1), (7, -2, -4, -1), (-7, -2, 3, -1), (6, -3, -2, 0)] {
+            assert_eq!(int(ArithmeticOp::FloorDivide, left, right), Ok(quotient), "{left} ~/ {right}");
+            assert_eq!(int(ArithmeticOp::Modulo, left, right), Ok(remainder), "{left} % {right}");
+            assert_eq!(float(ArithmeticOp::FloorDivide, left as f64, right as f64), quotient as f64);
+            assert_eq!(float(ArithmeticOp::Modulo, left as f64, right as f64), remainder as f64);
+        }
+    }
+
+    #[test]
+    fn integer_division_by_zero_is_an_error() {
+        assert_eq!(int(ArithmeticOp::FloorDivide, 1, 0), Err("Division by zero."));
+        assert_eq!(int(ArithmeticOp::Modulo, 1, 0), Err("Division by zero."));
+        assert_eq!(float(ArithmeticOp::Divide, 1.0, 0.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn mixed_operands_give_floats() {
+        assert!(matches!(Number::arithmetic(ArithmeticOp::Add, Number::Int(1), Number::Float(0.5)), Ok(Number::Float(value)) if value == 1.5));
+        assert!(matches!(Number::arithmetic(ArithmeticOp::Divide, Number::Int(6), Number::Int(3)), Ok(Number::Float(value)) if value == 2.0));
+        assert!(Number::equals(Number::Int(1), Number::Float(1.0)));
+        assert_eq!(Number::compare(Number::Int(1), Number::Float(f64::NAN)), None);
+    }
+}
@@ -1,18 +1,18 @@
-use crate::{expr::Expr, literal::Literal, token::Token, token_type::TokenType, error_handler::error, stmt::Stmt, };
+use crate::{expr::{self, Expr}, literal::Literal, token::Token, token_type::TokenType, error_handler::LoxError, stmt::Stmt, };
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
-    had_error: bool,
+    errors: Vec<LoxError>,
+    /// How many loops enclose the current statement within the current
+    /// function, so `break` and `continue` elsewhere can be rejected.
+    loop_depth: usize,
+    warnings: Vec<LoxError>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0, had_error: false }
-    }
-    
-    pub fn get_had_err(&self) -> bool {
-        self.had_error
+        Parser { tokens, current: 0, errors: Vec::new(), loop_depth: 0, warnings: Vec::new() }
     }
 
     fn synchronize(&mut self) {
@@ -40,39 +40,103 @@ impl Parser {
             self.advance();
         }
     }
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<LoxError>> {
         let mut statements: Vec<Stmt> = vec![];
         while !self.is_at_end() {
             if let Some(stmt) = self.declaration() {
                 statements.push(stmt);
             }
-            else {
-                self.had_error = true;
+        }
+
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        self.orphaned_docs();
+        Ok(statements)
+    }
+
+    /// Warnings about the source that don't stop it from running.
+    pub fn take_warnings(&mut self) -> Vec<LoxError> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Declarations take their doc comments off the tokens, so any doc left
+    /// documents nothing, like one before a `print`. It's dropped with a
+    /// warning rather than silently.
+    fn orphaned_docs(&mut self) {
+        for token in &self.tokens {
+            if token.doc.is_some() {
+                let warning = LoxError::warning(token.clone(), "The doc comment before this documents nothing: it isn't a declaration.");
+                self.warnings.push(warning.with_help("'///' documents the function, class, variable or method after it; use '//' here"));
+            }
+        }
+    }
+
+    fn declaration(&mut self) -> Option<Stmt> {
+        let declaration = if self.match_token(TokenType::Class) {
+            self.class_declaration()
+        }
+        else if self.match_token(TokenType::Fun) {
+            self.function("function")
+        }
+        else if self.match_token(TokenType::Var) {
+            self.var_declaration()
+        }
+        else {
+            self.statement()
+        };
+
+        match declaration {
+            Ok(stmt) => Some(stmt),
+            Err(error) => {
+                self.errors.push(error);
                 self.synchronize();
+                None
             }
         }
-        statements
+    }
 
+    /// Parses the tokens as a single expression with nothing after it, the
+    /// REPL's bare-expression input. Returns `None` for anything else.
+    pub fn parse_expression(&mut self) -> Option<Expr> {
+        let expression = self.expression().ok()?;
+        (self.is_at_end() && self.errors.is_empty()).then_some(*expression)
     }
 
-    fn declaration(&mut self) -> Option<Stmt> {
-        if self.match_token(TokenType::Fun) {
-            return self.function("function");
+    fn class_declaration(&mut self) -> Result<Stmt, LoxError> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+        let name = self.documented(name);
+
+        let mut superclass = None;
+        if self.match_token(TokenType::Less) {
+            let superclass_name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            if superclass_name.lexeme == name.lexeme {
+                return Err(self.error(superclass_name, "A class can't inherit from itself."));
+            }
+            superclass = Some(Expr::Variable(superclass_name, expr::next_id()));
         }
-        if self.match_token(TokenType::Var) {
-            return self.var_declaration();
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
         }
-        self.statement()
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class(name, superclass, methods))
     }
 
-    fn function(&mut self, kind: &str) -> Option<Stmt> {
+    fn function(&mut self, kind: &str) -> Result<Stmt, LoxError> {
         let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
+        let name = self.documented(name);
         self.consume(TokenType::LeftParen, &format!("Expect '(' after {} name.", kind))?;
         let mut parameters = Vec::new();
         if !self.check(TokenType::RightParen) {
             loop {
                 if parameters.len() >= 255 {
-                    self.error(self.peek(), "Can't have more than 255 parameters");
+                    let error = self.error(self.peek(), "Can't have more than 255 parameters");
+                    self.errors.push(error);
                 }
                 parameters.push(self.consume(TokenType::Identifier, "Expect paramter name.")?);
 
@@ -83,29 +147,35 @@ impl Parser {
         }
         self.consume(TokenType::RightParen, "Expect '(' after parameters.")?;
 
-        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {:?} body.", name))?;
+        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
 
-        let body = self.block()?;
-        Some(Stmt::Function(name, parameters, body))
+        // A loop around the declaration doesn't make `break` valid inside it.
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
+        let body = self.block();
+        self.loop_depth = enclosing_loops;
+        Ok(Stmt::Function(name, parameters, body?))
     }
 
-    fn var_declaration(&mut self) -> Option<Stmt> {
+    fn var_declaration(&mut self) -> Result<Stmt, LoxError> {
         let name = self.consume(TokenType::Identifier, "Expect variable name")?;
+        let name = self.documented(name);
         let mut initializer = None;
         if self.match_token(TokenType::Equal) {
             let expression = self.expression()?;
             initializer = Some(*expression);
         }
         self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.")?;
-        Some(Stmt::Var(name, initializer))
+        Ok(Stmt::Var(name, initializer))
     }
 
-    fn statement(&mut self) -> Option<Stmt> {
+    fn statement(&mut self) -> Result<Stmt, LoxError> {
         if self.match_token(TokenType::Print) {
             return self.print_statement();
         }
         if self.match_token(TokenType::LeftBrace) {
-            return Some(Stmt::Block(self.block()?));
+            let open = self.previous();
+            let statements = self.block()?;
+            return Ok(Stmt::Block(statements, open.span.merge(self.previous().span)));
         }
         if self.match_token(TokenType::If) {
             return self.if_statement();
@@ -119,53 +189,66 @@ impl Parser {
         if self.match_token(TokenType::Return) {
             return self.return_statement();
         }
+        if self.match_tokens(vec![TokenType::Break, TokenType::Continue]) {
+            return self.loop_jump_statement();
+        }
         self.expression_statement()
     }
 
-    fn return_statement(&mut self) -> Option<Stmt> {
+    fn return_statement(&mut self) -> Result<Stmt, LoxError> {
         let keyword = self.previous();
         let value = if !self.check(TokenType::Semicolon) {
             Some(*self.expression()?)
         } else { None };
         self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
-        Some(Stmt::Return(keyword, value))
+        Ok(Stmt::Return(keyword, value))
 
     }
 
-    fn if_statement(&mut self) -> Option<Stmt> {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.");
-        if let Some(condition) = self.expression() {
-            self.consume(TokenType::RightParen, "Expect ')' after 'if'.");
-
-            if let Some(then_branch) = self.statement() {
-                let mut else_branch = None;
-
-                if self.match_token(TokenType::Else) {
-
-                    else_branch = self.statement().map(Box::new);
+    fn loop_jump_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous();
+        if self.loop_depth == 0 {
+            let error = self.error(keyword.clone(), &format!("Can't use '{}' outside of a loop.", keyword.lexeme));
+            self.errors.push(error);
+        }
+        self.consume(TokenType::Semicolon, &format!("Expect ';' after '{}'.", keyword.lexeme))?;
+        if keyword.token_type == TokenType::Break {
+            Ok(Stmt::Break(keyword))
+        } else {
+            Ok(Stmt::Continue(keyword))
+        }
+    }
 
-                }
+    fn loop_body(&mut self) -> Result<Stmt, LoxError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
 
-                Some(Stmt::If(*condition, Box::new(then_branch), else_branch))
-            }
-            else {
+    fn if_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after 'if'.")?;
 
-                None
-            }
-        }
-        else {
-            None
+        let then_branch = self.statement()?;
+        let mut else_branch = None;
+        if self.match_token(TokenType::Else) {
+            else_branch = Some(Box::new(self.statement()?));
         }
+
+        Ok(Stmt::If(*condition, Box::new(then_branch), else_branch))
     }
 
-    fn while_statement(&mut self) -> Option<Stmt> {
+    fn while_statement(&mut self) -> Result<Stmt, LoxError> {
         self.consume(TokenType::LeftParen, "Expect '(' before 'while'")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after 'conditon'")?;
-        self.statement().map(|body| Stmt::While(*condition, Box::new(body)))
+        let body = self.loop_body()?;
+        Ok(Stmt::While(*condition, Box::new(body), None))
     }
 
-    fn block(&mut self) -> Option<Vec<Stmt>> {
+    fn block(&mut self) -> Result<Vec<Stmt>, LoxError> {
         let mut statements = Vec::new();
 
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
@@ -175,293 +258,267 @@ impl Parser {
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
-        Some(statements)
+        Ok(statements)
     }
 
-    fn for_statement(&mut self) -> Option<Stmt> {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
-        let initializer;
+    fn for_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
-        if self.match_token(TokenType::Semicolon) {
-            initializer = None;
+        let initializer = if self.match_token(TokenType::Semicolon) {
+            None
         }
         else if self.match_token(TokenType::Var) {
-            initializer = self.var_declaration();
-        }
-        else if let Some(init) = self.expression_statement() {
-            initializer = Some(init);
+            Some(self.var_declaration()?)
         }
         else {
-            initializer = None;
-        }
+            Some(self.expression_statement()?)
+        };
 
         let mut condition = None;
         if !self.check(TokenType::Semicolon) {
-            if let Some(cond) = self.expression() {
-                condition = Some(cond);
-            }
+            condition = Some(self.expression()?);
         }
-
-        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.");
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
 
         let mut increment = None;
-
         if !self.check(TokenType::RightParen) {
-            if let Some(incr) = self.expression() {
-                increment = Some(incr);
-            }
+            increment = Some(self.expression()?);
         }
+        self.consume(TokenType::RightParen, "Expected ')' after 'for'.")?;
 
-        self.consume(TokenType::RightParen, "Expected ')' after 'for'.");
+        let body = self.loop_body()?;
 
-        let mut body: Option<Stmt>;
+        let condition = condition.unwrap_or_else(|| Box::new(Expr::Literal(Literal::Bool(true), keyword.span)));
+        let mut body = Stmt::While(*condition, Box::new(body), increment.map(|increment| *increment));
 
-        if let Some(stmt) = self.statement() {
-            body = Some(stmt);
-
-            if let Some(inc) = increment {
-                body = Some(Stmt::Block(vec![body?, Stmt::Expr(*inc)]));
-            }
-
-            if let Some(condition) = condition {
-                body = Some(Stmt::While(*condition, Box::new(body?)));
-            }
-            else {
-                body = Some(Stmt::While(Expr::Literal(Literal::Bool(true)), Box::new(body?)));
-            }
-
-            if initializer.is_some() {
-                body = Some(Stmt::Block(vec![initializer?, body?]));
-            }
-        }
-        else {
-            body = None
+        if let Some(initializer) = initializer {
+            let span = keyword.span.merge(body.span());
+            body = Stmt::Block(vec![initializer, body], span);
         }
 
-        body
+        Ok(body)
     }
 
-    fn print_statement(&mut self) -> Option<Stmt> {
-        if let Some(value) = self.expression() {
-            self.consume(TokenType::Semicolon, "Expect ';' after value.");
-            return Some(Stmt::Print(*value));
-        }
-        None
+    fn print_statement(&mut self) -> Result<Stmt, LoxError> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(*value))
     }
 
-    fn expression_statement(&mut self) -> Option<Stmt> {
-        if let Some(expr) = self.expression() {
-            self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
-            return Some(Stmt::Expr(*expr));
-        }
-        None
+    fn expression_statement(&mut self) -> Result<Stmt, LoxError> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expr(*expr))
     }
 
-    fn expression(&mut self) -> Option<Box<Expr>> {
+    fn expression(&mut self) -> Result<Box<Expr>, LoxError> {
         self.assignment()
     }
 
-    fn assignment(&mut self) -> Option<Box<Expr>> {
-        let expr = self.or();
+    fn assignment(&mut self) -> Result<Box<Expr>, LoxError> {
+        let expr = self.or()?;
 
         if self.match_token(TokenType::Equal) {
             let equals = self.previous();
-            if let Some(value) = self.assignment() {
-                if let Expr::Variable(name) = *expr.clone()? {
-                    return Some(Box::new(Expr::Assign(name, value)));
-                }
-            }
-            else {
-                error::error(equals, "Invalid assignment target.");
-                return None;
-            }
+            let value = self.assignment()?;
+            return match *expr {
+                Expr::Variable(name, id) => Ok(Box::new(Expr::Assign(name, value, id))),
+                Expr::Get(object, name) => Ok(Box::new(Expr::Set(object, name, value))),
+                Expr::Index(object, bracket, index) => Ok(Box::new(Expr::IndexSet(object, bracket, index, value))),
+                _ => Err(self.error(equals, "Invalid assignment target.")),
+            };
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn or(&mut self) -> Option<Box<Expr>> {
-        if let Some(mut expr) = self.and() {
-            while self.match_token(TokenType::Or) {
-                let operator = self.previous();
-                if let Some(right) = self.and() {
-                    expr = Box::new(Expr::Logical(expr, operator, right));
-                }
-            }
-    
-            Some(expr)
-        }
-        else {
-            None
+    fn or(&mut self) -> Result<Box<Expr>, LoxError> {
+        let mut expr = self.and()?;
+        while self.match_token(TokenType::Or) {
+            let operator = self.previous();
+            let right = self.and()?;
+            expr = Box::new(Expr::Logical(expr, operator, right));
         }
+        Ok(expr)
     }
 
-    fn and(&mut self) -> Option<Box<Expr>> {
-        if let Some(mut expr) = self.equality() {
-            while self.match_token(TokenType::And) {
-                let operator = self.previous();
-                if let Some(right) = self.equality() {
-                    expr = Box::new(Expr::Logical(expr, operator, right));
-                }
-            }
-    
-            Some(expr)
-        }
-        else {
-            None
+    fn and(&mut self) -> Result<Box<Expr>, LoxError> {
+        let mut expr = self.equality()?;
+        while self.match_token(TokenType::And) {
+            let operator = self.previous();
+            let right = self.equality()?;
+            expr = Box::new(Expr::Logical(expr, operator, right));
         }
+        Ok(expr)
     }
 
-    fn equality(&mut self) -> Option<Box<Expr>> {
-        if let Some(mut expr) = self.comparison() {
-            while self.match_tokens(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
-                let operator = self.previous();
-                if let Some(right) = self.comparison() {
-                    expr = Box::new(Expr::Binary(expr, operator, right));
-                }
-            }
-    
-            Some(expr)
-        }
-        else {
-            None
+    fn equality(&mut self) -> Result<Box<Expr>, LoxError> {
+        let mut expr = self.comparison()?;
+        while self.match_tokens(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
+            let operator = self.previous();
+            let right = self.comparison()?;
+            expr = Box::new(Expr::Binary(expr, operator, right));
         }
-        
+        Ok(expr)
     }
 
-    fn comparison(&mut self) -> Option<Box<Expr>> {
-        if let Some(mut expr) = self.term() {
-            while self.match_tokens(vec![
-                TokenType::Greater,
-                TokenType::GreaterEqual,
-                TokenType::Less,
-                TokenType::LessEqual,
-            ]) {
-                let operator = self.previous();
-                if let Some(right) = self.term() {
-                    expr = Box::new(Expr::Binary(expr, operator, right));
-                }
-            }
-            Some(expr)
-        }
-        else {
-            None
+    fn comparison(&mut self) -> Result<Box<Expr>, LoxError> {
+        let mut expr = self.term()?;
+        while self.match_tokens(vec![
+            TokenType::Greater,
+            TokenType::GreaterEqual,
+            TokenType::Less,
+            TokenType::LessEqual,
+        ]) {
+            let operator = self.previous();
+            let right = self.term()?;
+            expr = Box::new(Expr::Binary(expr, operator, right));
         }
+        Ok(expr)
     }
 
-    fn term(&mut self) -> Option<Box<Expr>> {
-        if let Some(mut expr) = self.factor() {
-            while self.match_tokens(vec![TokenType::Minus, TokenType::Plus]) {
-                let operator = self.previous();
-                if let Some(right) = self.factor() {
-                    expr = Box::new(Expr::Binary(expr, operator, right));
-                }
-            }
-    
-            Some(expr)
-        }
-        else {
-            None
+    fn term(&mut self) -> Result<Box<Expr>, LoxError> {
+        let mut expr = self.factor()?;
+        while self.match_tokens(vec![TokenType::Minus, TokenType::Plus]) {
+            let operator = self.previous();
+            let right = self.factor()?;
+            expr = Box::new(Expr::Binary(expr, operator, right));
         }
+        Ok(expr)
     }
 
-    fn factor(&mut self) -> Option<Box<Expr>> {
-        if let Some(mut expr) = self.unary() {
-            while self.match_tokens(vec![TokenType::Slash, TokenType::Star]) {
-                let operator = self.previous();
-                if let Some(right) = self.unary() {
-                    expr = Box::new(Expr::Binary(expr, operator, right));
-                }
-            }
-    
-            Some(expr)
-        }
-        else {
-            None
+    fn factor(&mut self) -> Result<Box<Expr>, LoxError> {
+        let mut expr = self.unary()?;
+        while self.match_tokens(vec![TokenType::Slash, TokenType::TildeSlash, TokenType::Star, TokenType::Percent]) {
+            let operator = self.previous();
+            let right = self.unary()?;
+            expr = Box::new(Expr::Binary(expr, operator, right));
         }
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Option<Box<Expr>> {
+    fn unary(&mut self) -> Result<Box<Expr>, LoxError> {
         if self.match_tokens(vec![TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous();
-            if let Some(right) = self.unary() {
-                return Some(Box::new(Expr::Unary(operator, right)));
-            }
-            else {
-                self.error(operator, "Expected expression on the right hand side.");
-            }
+            let right = self.unary()?;
+            return Ok(Box::new(Expr::Unary(operator, right)));
         }
 
         self.call()
     }
 
-    fn call(&mut self) -> Option<Box<Expr>> {
-        if let Some(mut expr) = self.primary() {
-            loop {
-                if self.match_token(TokenType::LeftParen) {
-                    expr = Box::new(self.finish_call(*expr)?);
-                }
-                else {
-                    break;
-                }
+    fn call(&mut self) -> Result<Box<Expr>, LoxError> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_token(TokenType::LeftParen) {
+                expr = Box::new(self.finish_call(*expr)?);
+            }
+            else if self.match_token(TokenType::Dot) {
+                let name = self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Box::new(Expr::Get(expr, name));
+            }
+            else if self.match_token(TokenType::LeftBracket) {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Box::new(Expr::Index(expr, bracket, index));
+            }
+            else {
+                break;
             }
-            Some(expr)
-        }
-        else {
-            None
         }
+        Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> Option<Expr> {
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, LoxError> {
         let mut arguments = vec![];
 
         if !self.check(TokenType::RightParen) {
             loop {
 
                 if arguments.len() >= 255 {
-                    self.error(self.peek(), "Can't have more than 255 arguments.");
+                    let error = self.error(self.peek(), "Can't have more than 255 arguments.");
+                    self.errors.push(error);
                 }
 
-                arguments.push(self.expression());
+                arguments.push(*self.expression()?);
                 if !self.match_token(TokenType::Comma) { break; }
             }
         }
 
         let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments")?;
 
-        Some(Expr::Call(Box::new(callee), paren, arguments))
+        Ok(Expr::Call(Box::new(callee), paren, arguments))
     }
 
-    fn primary(&mut self) -> Option<Box<Expr>> {
+    fn primary(&mut self) -> Result<Box<Expr>, LoxError> {
         if self.match_token(TokenType::False) {
-            return Some(Box::new(Expr::Literal(Literal::Bool(false))));
+            return Ok(Box::new(Expr::Literal(Literal::Bool(false), self.previous().span)));
         }
         if self.match_token(TokenType::True) {
-            return Some(Box::new(Expr::Literal(Literal::Bool(true))));
+            return Ok(Box::new(Expr::Literal(Literal::Bool(true), self.previous().span)));
         }
         if self.match_token(TokenType::Nil) {
-            return Some(Box::new(Expr::Literal(Literal::Null)));
+            return Ok(Box::new(Expr::Literal(Literal::Null, self.previous().span)));
         }
 
+        if self.match_token(TokenType::Interpolation) {
+            return self.interpolation();
+        }
         if self.match_tokens(vec![TokenType::Number, TokenType::String]) {
-            return Some(Box::new(Expr::Literal(self.previous().literal?)));
+            let token = self.previous();
+            return Ok(Box::new(Expr::Literal(token.literal.unwrap(), token.span)));
         }
 
         if self.match_token(TokenType::LeftParen) {
-            if let Some(expr) = self.expression() {
-                self.consume(TokenType::RightParen, "Expect ')' after expression");
-                return Some(Box::new(Expr::Grouping(expr)));
+            let open = self.previous();
+            let expr = self.expression()?;
+            let close = self.consume(TokenType::RightParen, "Expect ')' after expression")?;
+            return Ok(Box::new(Expr::Grouping(expr, open.span.merge(close.span))));
+        }
+        if self.match_token(TokenType::LeftBracket) {
+            let open = self.previous();
+            let mut items = vec![];
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    items.push(*self.expression()?);
+                    if !self.match_token(TokenType::Comma) { break; }
+                }
             }
-            else {
-                return None;
+            let close = self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+            return Ok(Box::new(Expr::List(open, items, close)));
+        }
+        // A `{` only reaches here in expression position; at the start of a
+        // statement `statement` has already taken it as a block.
+        if self.match_token(TokenType::LeftBrace) {
+            let open = self.previous();
+            let mut entries = vec![];
+            if !self.check(TokenType::RightBrace) {
+                loop {
+                    let key = self.expression()?;
+                    self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                    let value = self.expression()?;
+                    entries.push((*key, *value));
+                    if !self.match_token(TokenType::Comma) { break; }
+                }
             }
+            let close = self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+            return Ok(Box::new(Expr::Map(open, entries, close)));
+        }
+        if self.match_token(TokenType::Super) {
+            let keyword = self.previous();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(Box::new(Expr::Super(keyword, method, expr::next_id())));
+        }
+        if self.match_token(TokenType::This) {
+            return Ok(Box::new(Expr::This(self.previous(), expr::next_id())));
         }
         if self.match_token(TokenType::Identifier) {
-            return Some(Box::new(Expr::Variable(self.previous())));
+            return Ok(Box::new(Expr::Variable(self.previous(), expr::next_id())));
         }
 
-        self.error(self.peek(), "Expect expression.");
-        None
+        Err(self.error(self.peek(), "Expect expression."))
     }
 
     fn match_token(&mut self, token_type: TokenType) -> bool {
@@ -495,13 +552,59 @@ impl Parser {
         self.previous()
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Option<Token> {
+    /// `"a ${b} c"` arrives as the segment `"a ${`, the tokens of `b` and the
+    /// segment `} c"`, and is desugared into `"a " + str(b) + " c"` where
+    /// `str` is the `Stringify` conversion. Empty segments are left out.
+    fn interpolation(&mut self) -> Result<Box<Expr>, LoxError> {
+        let mut parts = vec![];
+        let mut segment = self.previous();
+        loop {
+            if let Some(Literal::String(text)) = &segment.literal {
+                if !text.is_empty() {
+                    parts.push((Expr::Literal(Literal::String(text.clone()), segment.span), segment.span));
+                }
+            }
+            if segment.token_type == TokenType::String {
+                break;
+            }
+            let value = self.expression()?;
+            parts.push((Expr::Stringify(value), segment.span));
+            segment = if self.match_token(TokenType::Interpolation) {
+                self.previous()
+            } else {
+                self.consume(TokenType::String, "Expect '}' after interpolated expression.")?
+            };
+        }
+
+        let mut parts = parts.into_iter();
+        let (mut expr, _) = parts.next().expect("an interpolation has at least one expression");
+        for (part, span) in parts {
+            let plus = Token::new(TokenType::Plus, String::from("+"), None, span);
+            expr = Expr::Binary(Box::new(expr), plus, Box::new(part));
+        }
+        Ok(Box::new(expr))
+    }
+
+    /// Doc comments end up on the token after them: the `fun`, `class` or
+    /// `var` keyword, or a method's own name. Either way the declared name
+    /// carries them in the tree, and they're taken off the tokens so that
+    /// `orphaned_docs` only sees the rest.
+    fn documented(&mut self, mut name: Token) -> Token {
+        let name_doc = self.tokens[self.current - 1].doc.take();
+        let keyword = &mut self.tokens[self.current - 2];
+        let keyword_doc = match keyword.token_type {
+            TokenType::Fun | TokenType::Class | TokenType::Var => keyword.doc.take(),
+            _ => None,
+        };
+        name.doc = name_doc.or(keyword_doc);
+        name
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, LoxError> {
         if self.check(token_type) {
-            return Some(self.advance());
+            return Ok(self.advance());
         }
-        self.error(self.peek(), message);
-        None
-
+        Err(self.error(self.peek(), message))
     }
 
     fn is_at_end(&self) -> bool {
@@ -516,7 +619,7 @@ impl Parser {
         self.tokens.get(self.current - 1).unwrap().clone()
     }
 
-    fn error(&self, token: Token, message: &str){
-        error::error(token, message);
+    fn error(&self, token: Token, message: &str) -> LoxError {
+        LoxError::parse(token, message)
     }
-}
\ No newline at end of file
+}
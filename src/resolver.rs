@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use crate::{expr::{Expr, ExprId}, stmt::Stmt, token::Token, interpreter::Interpreter, error_handler::LoxError};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<LoxError>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Resolver {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, stmts: &[Stmt]) -> Result<(), Vec<LoxError>> {
+        self.resolve_statements(stmts);
+
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        Ok(())
+    }
+
+    fn resolve_statements(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.resolve_statement(stmt);
+        }
+    }
+
+    fn resolve_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(statements, _) => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            },
+            Stmt::Var(name, initializer) => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name);
+            },
+            Stmt::Function(name, _, _) => {
+                self.declare(name);
+                self.define(name);
+                self.resolve_function(stmt, FunctionType::Function);
+            },
+            Stmt::Expr(expression) | Stmt::Print(expression) => self.resolve_expression(expression),
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            },
+            Stmt::While(condition, body, increment) => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+                if let Some(increment) = increment {
+                    self.resolve_expression(increment);
+                }
+            },
+            Stmt::Break(_) | Stmt::Continue(_) => (),
+            Stmt::Return(keyword, value) => {
+                if self.current_function == FunctionType::None {
+                    let error = LoxError::resolve(keyword.clone(), "Can't return from top-level code.");
+                    self.errors.push(error.with_help("'return' is only allowed inside a function or method"));
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        let error = LoxError::resolve(keyword.clone(), "Can't return a value from an initializer.");
+                        self.errors.push(error.with_span(stmt.span()));
+                    }
+                    self.resolve_expression(value);
+                }
+            },
+            Stmt::Class(name, superclass, methods) => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expression(superclass);
+                    self.begin_scope();
+                    self.scopes.last_mut().unwrap().insert("super".to_string(), true);
+                }
+
+                self.begin_scope();
+                self.scopes.last_mut().unwrap().insert("this".to_string(), true);
+
+                for method in methods {
+                    if let Stmt::Function(method_name, _, _) = method {
+                        let declaration = if method_name.lexeme == "init" {
+                            FunctionType::Initializer
+                        } else {
+                            FunctionType::Method
+                        };
+                        self.resolve_function(method, declaration);
+                    }
+                }
+
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            },
+        }
+    }
+
+    fn resolve_expression(&mut self, expression: &Expr) {
+        match expression {
+            Expr::Variable(name, id) => {
+                if let Some(scope) = self.scopes.last() {
+                    if scope.get(&name.lexeme) == Some(&false) {
+                        let error = LoxError::resolve(name.clone(), "Can't read local variable in its own initializer.");
+                        self.errors.push(error.with_help("give the new variable a different name"));
+                    }
+                }
+                self.resolve_local(*id, name);
+            },
+            Expr::Assign(name, value, id) => {
+                self.resolve_expression(value);
+                self.resolve_local(*id, name);
+            },
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            },
+            Expr::Unary(_, right) => self.resolve_expression(right),
+            Expr::Grouping(expression, _) | Expr::Stringify(expression) => self.resolve_expression(expression),
+            Expr::Literal(..) => (),
+            Expr::Call(callee, _, arguments) => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            },
+            Expr::Get(object, _) => self.resolve_expression(object),
+            Expr::List(_, items, _) => {
+                for item in items {
+                    self.resolve_expression(item);
+                }
+            },
+            Expr::Map(_, entries, _) => {
+                for (key, value) in entries {
+                    self.resolve_expression(key);
+                    self.resolve_expression(value);
+                }
+            },
+            Expr::Index(object, _, index) => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+            },
+            Expr::IndexSet(object, _, index, value) => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+                self.resolve_expression(value);
+            },
+            Expr::Set(object, _, value) => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            },
+            Expr::This(keyword, id) => {
+                if self.current_class == ClassType::None {
+                    let error = LoxError::resolve(keyword.clone(), "Can't use 'this' outside of a class.");
+                    self.errors.push(error.with_help("'this' is only available inside methods"));
+                    return;
+                }
+                self.resolve_local(*id, keyword);
+            },
+            Expr::Super(keyword, _, id) => {
+                match self.current_class {
+                    ClassType::None => self.error(keyword, "Can't use 'super' outside of a class."),
+                    ClassType::Class => self.error(keyword, "Can't use 'super' in a class with no superclass."),
+                    ClassType::Subclass => (),
+                }
+                self.resolve_local(*id, keyword);
+            },
+        }
+    }
+
+    fn resolve_function(&mut self, function: &Stmt, function_type: FunctionType) {
+        if let Stmt::Function(_, params, body) = function {
+            let enclosing_function = self.current_function;
+            self.current_function = function_type;
+
+            self.begin_scope();
+            for param in params {
+                self.declare(param);
+                self.define(param);
+            }
+            self.resolve_statements(body);
+            self.end_scope();
+
+            self.current_function = enclosing_function;
+        }
+    }
+
+    fn resolve_local(&mut self, id: ExprId, name: &Token) {
+        for (i, scope) in self.scopes.iter().enumerate().rev() {
+            if scope.contains_key(&name.lexeme) {
+                self.interpreter.resolve(id, self.scopes.len() - 1 - i);
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last() {
+            if scope.contains_key(&name.lexeme) {
+                self.error(name, "Already a variable with this name in this scope.");
+            }
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), false);
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(LoxError::resolve(token.clone(), message));
+    }
+}
@@ -0,0 +1,42 @@
+use std::{collections::HashMap, rc::Rc, cell::RefCell};
+
+use crate::{rlox_function::RloxFunction, rlox_instance::RloxInstance, interpreter::Interpreter, literal::{Literal, TCallable}, error_handler::LoxError};
+
+#[derive(Debug)]
+pub struct RloxClass {
+    pub name: String,
+    superclass: Option<Rc<RloxClass>>,
+    methods: HashMap<String, Rc<RloxFunction>>,
+}
+
+impl RloxClass {
+    pub fn new(name: String, superclass: Option<Rc<RloxClass>>, methods: HashMap<String, Rc<RloxFunction>>) -> Self {
+        RloxClass { name, superclass, methods }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<RloxFunction>> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(Rc::clone(method));
+        }
+
+        match &self.superclass {
+            Some(superclass) => superclass.find_method(name),
+            None => None,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    pub fn instantiate(class: &Rc<RloxClass>, interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+        let instance = Rc::new(RefCell::new(RloxInstance::new(Rc::clone(class))));
+        if let Some(initializer) = class.find_method("init") {
+            initializer.bind(Rc::clone(&instance)).call(interpreter, arguments)?;
+        }
+        Ok(Literal::Instance(instance))
+    }
+}
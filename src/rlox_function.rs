@@ -1,18 +1,27 @@
 use std::{rc::Rc, cell::RefCell};
 
-use crate::{stmt::Stmt, interpreter::{Interpreter}, literal::{Literal, TCallable}, environment::Environment};
+use crate::{stmt::Stmt, interpreter::{Interpreter, Unwind}, literal::{Literal, TCallable}, environment::Environment, rlox_instance::RloxInstance, error_handler::LoxError};
 #[derive(Debug)]
 pub struct RloxFunction {
     declaration: Stmt,
     closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
 }
 
 impl RloxFunction {
-    pub fn new(declaration: Stmt, closure: Rc<RefCell<Environment>>) -> Self {
-        RloxFunction { declaration , closure }
+    pub fn new(declaration: Stmt, closure: Rc<RefCell<Environment>>, is_initializer: bool) -> Self {
+        RloxFunction { declaration , closure, is_initializer }
     }
 
-    
+    pub fn bind(&self, instance: Rc<RefCell<RloxInstance>>) -> RloxFunction {
+        let environment = Environment::from_existing(Rc::clone(&self.closure));
+        environment.borrow_mut().define("this".to_string(), Some(Literal::Instance(instance)));
+        RloxFunction::new(self.declaration.clone(), environment, self.is_initializer)
+    }
+
+    fn this(&self) -> Literal {
+        self.closure.borrow().get_at(0, "this").unwrap_or(Literal::Null)
+    }
 }
 
 impl TCallable for RloxFunction {
@@ -22,16 +31,27 @@ impl TCallable for RloxFunction {
         } else { 0 }
     }
 
-    fn call(&self, interpreter: &mut Interpreter, arguments: &[Option<Literal>]) -> Option<Literal> {
+    fn call(&self, interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
         let environment = Environment::from_existing(Rc::clone(&self.closure));
         if let Stmt::Function(_, params, body) = &self.declaration {
-            for i in 0..params.len() {
-                environment.borrow_mut().define(params.get(i)?.lexeme.clone(), arguments.get(i).unwrap().clone());
+            for (param, argument) in params.iter().zip(arguments) {
+                environment.borrow_mut().define(param.lexeme.clone(), Some(argument.clone()));
             }
-            if let Err(value) = interpreter.execute_block(body, environment) {
-                return value;
+            match interpreter.execute_block(body, environment) {
+                Ok(()) => (),
+                Err(Unwind::Return(value)) => {
+                    if self.is_initializer {
+                        return Ok(self.this());
+                    }
+                    return Ok(value);
+                },
+                Err(Unwind::Error(error)) => return Err(error),
+                Err(unwind) => unreachable!("the parser keeps {unwind:?} inside loops"),
             }
         }
-        None
+        if self.is_initializer {
+            return Ok(self.this());
+        }
+        Ok(Literal::Null)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,31 @@
+use std::{collections::HashMap, rc::Rc, cell::RefCell};
+
+use crate::{rlox_class::RloxClass, literal::Literal, token::Token, error_handler::LoxError};
+
+#[derive(Debug)]
+pub struct RloxInstance {
+    pub class: Rc<RloxClass>,
+    fields: HashMap<String, Literal>,
+}
+
+impl RloxInstance {
+    pub fn new(class: Rc<RloxClass>) -> Self {
+        RloxInstance { class, fields: HashMap::new() }
+    }
+
+    pub fn get(instance: &Rc<RefCell<RloxInstance>>, name: &Token) -> Result<Literal, LoxError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        if let Some(method) = instance.borrow().class.find_method(&name.lexeme) {
+            return Ok(Literal::Callable(Rc::new(method.bind(Rc::clone(instance)))));
+        }
+
+        Err(LoxError::runtime(name, &format!("Undefined property '{}'.", name.lexeme)))
+    }
+
+    pub fn set(&mut self, name: &Token, value: Literal) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
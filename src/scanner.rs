@@ -1,25 +1,67 @@
-use crate::{token_type::TokenType, token::Token, literal::Literal, keywords::KEYWORDS, error_handler::error};
+use crate::{token_type::TokenType, token::{Token, Span}, literal::Literal, keywords::KEYWORDS, error_handler::LoxError};
 pub struct Scanner{
     source: String,
     tokens: Vec<Token>,
+    /// Byte offsets into `source`, always on char boundaries. Columns count
+    /// chars, so they match what an editor shows.
     start: usize,
     current: usize,
     line: usize,
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    errors: Vec<LoxError>,
+    /// One entry per string interpolation we are inside of, counting the
+    /// braces opened since its `${` so the `}` that ends it can be found.
+    interpolations: Vec<usize>,
+    /// `///` lines waiting to be attached to the next token.
+    doc: Option<String>,
+    /// Set when the source ends inside a string or block comment.
+    unterminated: bool,
+    /// Where every comment is, doc comments included, for the formatter.
+    comments: Vec<Span>,
 }
 
 impl Scanner{
     pub fn new(source: String) -> Scanner {
-        Scanner{source, tokens: vec![], start: 0, current: 0, line: 1}
+        Scanner{source, tokens: vec![], start: 0, current: 0, line: 1, column: 1, start_line: 1, start_column: 1, errors: vec![], interpolations: vec![], doc: None, unterminated: false, comments: vec![]}
     }
 
-    pub fn scan_tokens(&mut self) -> &Vec<Token> {
+    pub fn scan_tokens(&mut self) -> Result<&Vec<Token>, Vec<LoxError>> {
         while !self.is_at_end(){
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.scan_token();
         }
 
-        self.tokens.push(Token::new(TokenType::Eof, String::new(), Option::None, self.line));
-        &self.tokens
+        let eof = Span::new(self.current, self.current, self.line, self.column);
+        let mut end = Token::new(TokenType::Eof, String::new(), Option::None, eof);
+        end.doc = self.doc.take();
+        self.tokens.push(end);
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        Ok(&self.tokens)
+    }
+
+    /// The comments skipped by `scan_tokens`, in source order.
+    pub fn comments(&self) -> &[Span] {
+        &self.comments
+    }
+
+    /// Whether `source` stops partway through a statement: inside an open
+    /// bracket, string, interpolation or block comment. The REPL keeps
+    /// reading lines until it doesn't.
+    pub fn is_incomplete(source: &str) -> bool {
+        let mut scanner = Scanner::new(source.to_string());
+        let _ = scanner.scan_tokens();
+        let depth = scanner.tokens.iter().fold(0isize, |depth, token| match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => depth + 1,
+            TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => depth - 1,
+            _ => depth,
+        });
+        scanner.unterminated || !scanner.interpolations.is_empty() || depth > 0
     }
 
     fn scan_token(&mut self){
@@ -27,70 +69,109 @@ impl Scanner{
         match c {
             '(' => self.add_token_with_no_literal(TokenType::LeftParen),
             ')' => self.add_token_with_no_literal(TokenType::RightParen),
-            '{' => self.add_token_with_no_literal(TokenType::LeftBrace),
-            '}' => self.add_token_with_no_literal(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token_with_no_literal(TokenType::LeftBrace);
+            },
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string();
+                },
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token_with_no_literal(TokenType::RightBrace);
+                },
+                None => self.add_token_with_no_literal(TokenType::RightBrace),
+            },
+            '[' => self.add_token_with_no_literal(TokenType::LeftBracket),
+            ']' => self.add_token_with_no_literal(TokenType::RightBracket),
             ',' => self.add_token_with_no_literal(TokenType::Comma),
             '.' => self.add_token_with_no_literal(TokenType::Dot),
             '-' => self.add_token_with_no_literal(TokenType::Minus),
             '+' => self.add_token_with_no_literal(TokenType::Plus),
             ';' => self.add_token_with_no_literal(TokenType::Semicolon),
             '*' => self.add_token_with_no_literal(TokenType::Star),
+            ':' => self.add_token_with_no_literal(TokenType::Colon),
+            '%' => self.add_token_with_no_literal(TokenType::Percent),
+            // Floor division. Spelled `~/` because `//` always starts a comment.
+            '~' if self.match_token('/') => self.add_token_with_no_literal(TokenType::TildeSlash),
             '!' => self.add_matched_token('=', TokenType::BangEqual, TokenType::Bang, Option::None),
             '=' => self.add_matched_token('=', TokenType::EqualEqual, TokenType::Equal, Option::None),
             '<' => self.add_matched_token('=', TokenType::LessEqual, TokenType::Less, Option::None),
             '>' => self.add_matched_token('=', TokenType::GreaterEqual, TokenType::Greater, Option::None),
             '/' => {
-                if self.match_token('/'){
+                if self.peek() == '/' && self.peek_next() == '/' && self.peek_at(2) != '/' {
+                    self.doc_comment();
+                    self.comments.push(self.span());
+                }
+                else if self.match_token('/'){
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
-                    } 
+                    }
+                    self.comments.push(self.span());
                 }
                 else if self.match_token('*'){
                     self.multiline_comment();
+                    self.comments.push(self.span());
                 }
                 else{
                     self.add_token_with_no_literal(TokenType::Slash);
                 }
             }
-            ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            ' ' | '\r' | '\t' | '\n' => (),
             '"' => self.string(),
+            'r' if self.raw_string_follows() => self.raw_string(),
             '0'..='9' => self.number(),
             _ => {
-                if self.is_alphanumeric(c){
+                if self.is_alpha(c){
                     self.identifier();
                 }
                 else{
-                    //error::error(self.tokens.last().unwrap().clone(), "Unexpected character");
+                    self.error(&format!("Unexpected character '{}'.", c.escape_debug()), "this character is not part of Lox; remove it");
                 }
             }
         }
     }
 
     fn advance(&mut self) -> char{
-        self.current += 1;
-        self.source.chars().nth(self.current - 1).unwrap()
+        let c = self.peek();
+        self.current += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        }
+        else {
+            self.column += 1;
+        }
+        c
+    }
+
+    fn span(&self) -> Span {
+        Span::new(self.start, self.current, self.start_line, self.start_column)
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>){
         let text = &self.source[self.start..self.current];
-        self.tokens.push(Token::new(token_type, text.to_string(), literal, self.line));
+        let mut token = Token::new(token_type, text.to_string(), literal, self.span());
+        token.doc = self.doc.take();
+        self.tokens.push(token);
     }
 
     fn add_token_with_no_literal(&mut self, token_type: TokenType){
-       
-        let text = &self.source[self.start..self.current];
-        self.tokens.push(Token::new(token_type, text.to_string(), Option::None, self.line));
+        self.add_token(token_type, Option::None);
     }
 
     fn match_token(&mut self, expected: char) -> bool{
         if self.is_at_end(){
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != expected{
+        if self.peek() != expected{
             return false
         }
-        self.current += 1;
+        self.advance();
         true
     }
 
@@ -101,36 +182,131 @@ impl Scanner{
     }
 
     fn peek(&self) -> char{
-        if self.is_at_end(){
-            return '\0';
-        }
-        self.source.chars().nth(self.current).unwrap()
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char{
-        if self.current + 1 >= self.source.len(){
-            return '\0';
-        }
-        self.source.chars().nth(self.current + 1).unwrap()
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, distance: usize) -> char{
+        self.source[self.current..].chars().nth(distance).unwrap_or('\0')
     }
 
+    /// Scans the rest of a string after its opening `"`, or after the `}`
+    /// that ends an interpolated expression. A `${` ends the segment: it is
+    /// emitted as an `Interpolation` token and scanning goes back to ordinary
+    /// tokens until the matching `}`, which the parser then desugars.
     fn string(&mut self){
-        while self.peek() != '"' && !self.is_at_end(){
-            if self.peek() == '\n'{
-                self.line += 1;
+        let mut value = String::new();
+        loop {
+            if self.is_at_end(){
+                self.unterminated = true;
+                self.error("Unterminated string.", "add a closing '\"' to end the string");
+                return;
+            }
+            match self.advance() {
+                '"' => break,
+                '$' if self.peek() == '{' => {
+                    self.advance();
+                    self.interpolations.push(0);
+                    self.add_token(TokenType::Interpolation, Some(Literal::String(value)));
+                    return;
+                },
+                '\\' => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                },
+                c => value.push(c),
             }
-            self.advance();
         }
+        self.add_token(TokenType::String, Some(Literal::String(value)));
+    }
 
-        if self.is_at_end(){
-            error::error(self.tokens.last().unwrap().clone(), "Unterminated string");
+    /// Decodes the escape sequence after a `\`. Invalid escapes are reported
+    /// and dropped so the rest of the string is still scanned.
+    fn escape(&mut self) -> Option<char> {
+        let (start, line, column) = (self.current - 1, self.line, self.column - 1);
+        if self.is_at_end() {
+            return None;
         }
-        else{
-            self.advance();
+        let escaped = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            'u' => {
+                let c = self.unicode_escape();
+                if c.is_none() {
+                    let span = Span::new(start, self.current, line, column);
+                    self.error_at(span, "Invalid Unicode escape.", "write the code point as '\\u{...}' with one to six hex digits");
+                }
+                return c;
+            },
+            other => {
+                let span = Span::new(start, self.current, line, column);
+                self.error_at(span, &format!("Invalid escape sequence '\\{other}'."), "valid escapes are \\n \\t \\r \\0 \\\" \\\\ \\$ and \\u{...}");
+                return None;
+            },
+        };
+        Some(escaped)
+    }
+
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.match_token('{') {
+            return None;
+        }
+        let mut digits = String::new();
+        while self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+        if !self.match_token('}') || !(1..=6).contains(&digits.len()) {
+            return None;
+        }
+        u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)
+    }
+
+    /// Whether the `r` just scanned starts a raw string: `r"..."`, or
+    /// `r#"..."#` with any number of `#`s when the text contains quotes.
+    fn raw_string_follows(&self) -> bool {
+        let mut distance = 0;
+        while self.peek_at(distance) == '#' {
+            distance += 1;
+        }
+        self.peek_at(distance) == '"'
+    }
 
-            let value = &self.source[self.start + 1..self.current - 1];
-            self.add_token(TokenType::String, Option::Some(Literal::String(value.to_string())));
+    /// Raw strings have no escapes or interpolation and end at the first `"`
+    /// followed by as many `#`s as they started with.
+    fn raw_string(&mut self){
+        let mut hashes = 0;
+        while self.match_token('#') {
+            hashes += 1;
+        }
+        self.advance();
+
+        let mut value = String::new();
+        loop {
+            if self.is_at_end(){
+                let help = format!("add a closing '\"{}' to end the raw string", "#".repeat(hashes));
+                self.unterminated = true;
+                self.error("Unterminated raw string.", &help);
+                return;
+            }
+            let c = self.advance();
+            if c == '"' && (0..hashes).all(|distance| self.peek_at(distance) == '#') {
+                for _ in 0..hashes {
+                    self.advance();
+                }
+                break;
+            }
+            value.push(c);
         }
+        self.add_token(TokenType::String, Some(Literal::String(value)));
     }
 
     fn number(&mut self){
@@ -144,9 +320,14 @@ impl Scanner{
             while self.is_digit(self.peek()) {
                 self.advance();
             }
+            let number = self.source[self.start..self.current].parse::<f64>().unwrap();
+            return self.add_token(TokenType::Number, Some(Literal::Float(number)));
+        }
+
+        match self.source[self.start..self.current].parse::<i64>() {
+            Ok(number) => self.add_token(TokenType::Number, Some(Literal::Int(number))),
+            Err(_) => self.error("Integer literal is too large.", "integers must fit in 64 bits; add '.0' to make it a float"),
         }
-        let number = self.source[self.start..self.current].parse::<f32>().unwrap();
-        self.add_token(TokenType::Number, Some(Literal::Float(number)))
     }
 
     fn identifier(&mut self){
@@ -158,44 +339,137 @@ impl Scanner{
         self.add_token_with_no_literal(token_type.clone());
     }
 
+    /// Block comments nest, so a commented-out block may itself contain
+    /// `/* ... */` comments.
     fn multiline_comment(&mut self){
-        while self.peek() != '*' && !self.is_at_end(){
-            if self.peek() == '\n'{
-                self.line += 1;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end(){
+                self.unterminated = true;
+                self.error("Unterminated multiline comment.", "close the comment with '*/'");
+                return;
+            }
+            match self.advance() {
+                '/' if self.peek() == '*' => {
+                    self.advance();
+                    depth += 1;
+                },
+                '*' if self.peek() == '/' => {
+                    self.advance();
+                    depth -= 1;
+                },
+                _ => (),
             }
-            self.advance();
         }
+    }
 
-        if self.is_at_end(){
-            error::error(self.tokens.last().unwrap().clone(), "Unterminated multiline comment");
-        }
-        else{
+    /// A `///` line documents the declaration after it. Consecutive lines
+    /// are joined; `////` and longer are ordinary comments.
+    fn doc_comment(&mut self){
+        self.advance();
+        self.advance();
+        let start = self.current;
+        while self.peek() != '\n' && !self.is_at_end() {
             self.advance();
-
-            if self.peek() == '/'{
-                self.advance();
-            }
-            
-            else{
-                error::error(self.tokens.last().unwrap().clone(), "Unterminated multiline comment");
-            }
+        }
+        let text = self.source[start..self.current].trim_end();
+        let text = text.strip_prefix(' ').unwrap_or(text);
+        match &mut self.doc {
+            Some(doc) => {
+                doc.push('\n');
+                doc.push_str(text);
+            },
+            None => self.doc = Some(text.to_string()),
         }
     }
 
+    fn error(&mut self, message: &str, help: &str){
+        self.error_at(self.span(), message, help);
+    }
+
+    fn error_at(&mut self, span: Span, message: &str, help: &str){
+        let text = &self.source[span.start..span.end];
+        let token = Token::new(TokenType::Error, text.to_string(), Option::None, span);
+        self.errors.push(LoxError::scan(token, message).with_help(help));
+    }
+
     fn is_digit(&self, c: char) -> bool{
         c.is_ascii_digit()
     }
 
+    /// Identifiers may use any Unicode letter, so `café` and `名前` work.
+    /// Identifiers follow Unicode's XID rules, like Rust's: a letter or `_`,
+    /// then letters, digits, combining marks and connectors. Characters such
+    /// as `²` or `½` are numeric but not digits, and can't appear.
     fn is_alpha(&self, c: char) -> bool{
-        c.is_ascii_lowercase() || c.is_ascii_uppercase() || c == '_'
+        unicode_ident::is_xid_start(c) || c == '_'
     }
 
     fn is_alphanumeric(&self, c: char) -> bool{
-        self.is_alpha(c) || self.is_digit(c)
+        unicode_ident::is_xid_continue(c)
     }
     
     fn is_at_end(&self) -> bool{
         self.current >= self.source.len()
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(source: &str) -> Result<Vec<Token>, Vec<LoxError>> {
+        Scanner::new(source.to_string()).scan_tokens().cloned()
+    }
+
+    fn string(source: &str) -> String {
+        match &scan(source).expect("the source scans")[0].literal {
+            Some(Literal::String(value)) => value.clone(),
+            literal => panic!("expected a string, got {literal:?}"),
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string(r#""a\nb\tc\rd\0e""#), "a\nb\tc\rd\0e");
+        assert_eq!(string(r#""\"\\\$""#), "\"\\$");
+        assert_eq!(string(r#""\u{41}\u{e9}\u{1F600}""#), "Aé😀");
+    }
+
+    #[test]
+    fn invalid_escapes_are_errors() {
+        for source in [r#""\q""#, r#""\u{}""#, r#""\u{1234567}""#, r#""\u{D800}""#, r#""\u41""#] {
+            let errors = scan(source).expect_err(source);
+            assert_eq!(errors.len(), 1, "{source}");
+        }
+    }
+
+    #[test]
+    fn unicode_source() {
+        let tokens = scan("var café = \"naïve 😀\"; print café;").expect("the source scans");
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].lexeme, "café");
+        assert_eq!(tokens[3].literal, Some(Literal::String(String::from("naïve 😀"))));
+        // Columns count characters, offsets count bytes.
+        assert_eq!(tokens[5].span.column, 23);
+        assert_eq!(tokens[5].span.start, "var café = \"naïve 😀\"; ".len());
+    }
+
+    #[test]
+    fn identifiers() {
+        let tokens = scan("x_1 _ é Ⅻ").expect("the source scans");
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(lexemes, ["x_1", "_", "é", "Ⅻ", ""]);
+        assert!(scan("x²").is_err());
+        assert!(scan("half½").is_err());
+    }
+
+    #[test]
+    fn comments() {
+        let tokens = scan("/* outer /* inner */ still */ 1 // 2\n~/ 3").expect("the source scans");
+        let types: Vec<TokenType> = tokens.iter().map(|token| token.token_type.clone()).collect();
+        assert_eq!(types, [TokenType::Number, TokenType::TildeSlash, TokenType::Number, TokenType::Eof]);
+        assert!(scan("/* /* */").is_err());
+    }
+}
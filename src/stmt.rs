@@ -1,13 +1,67 @@
-use crate::{expr::Expr, token::Token};
+use crate::{expr::Expr, token::{Token, Span}};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Stmt {
     Expr(Expr),
     Print(Expr),
     Var(Token, Option<Expr>),
-    Block(Vec<Stmt>),
+    /// The statements and the span from `{` to `}`.
+    Block(Vec<Stmt>, Span),
     If(Expr, Box<Stmt>, Option<Box<Stmt>>),
-    While(Expr, Box<Stmt>),
+    /// The condition, the body and, for desugared `for` loops, the increment,
+    /// kept apart from the body so `continue` still runs it.
+    While(Expr, Box<Stmt>, Option<Expr>),
+    Break(Token),
+    Continue(Token),
     Function(Token, Vec<Token>, Vec<Stmt>),
-    Return(Token, Option<Expr>)
-}
\ No newline at end of file
+    Return(Token, Option<Expr>),
+    Class(Token, Option<Expr>, Vec<Stmt>)
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Expr(expression) | Stmt::Print(expression) => expression.span(),
+            Stmt::Var(name, initializer) => match initializer {
+                Some(initializer) => name.span.merge(initializer.span()),
+                None => name.span,
+            },
+            Stmt::Block(_, span) => *span,
+            Stmt::If(condition, then_branch, else_branch) => {
+                let span = condition.span().merge(then_branch.span());
+                match else_branch {
+                    Some(else_branch) => span.merge(else_branch.span()),
+                    None => span,
+                }
+            },
+            Stmt::While(condition, body, _) => condition.span().merge(body.span()),
+            Stmt::Break(keyword) | Stmt::Continue(keyword) => keyword.span,
+            Stmt::Function(name, _, body) => match Stmt::span_of(body) {
+                Some(span) => name.span.merge(span),
+                None => name.span,
+            },
+            Stmt::Return(keyword, value) => match value {
+                Some(value) => keyword.span.merge(value.span()),
+                None => keyword.span,
+            },
+            Stmt::Class(name, _, methods) => match Stmt::span_of(methods) {
+                Some(span) => name.span.merge(span),
+                None => name.span,
+            },
+        }
+    }
+
+    /// The `///` comment before a function, class, variable or method.
+    pub fn doc(&self) -> Option<&str> {
+        match self {
+            Stmt::Var(name, _) | Stmt::Function(name, _, _) | Stmt::Class(name, _, _) => name.doc.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn span_of(statements: &[Stmt]) -> Option<Span> {
+        let first = statements.first()?.span();
+        let last = statements.last()?.span();
+        Some(first.merge(last))
+    }
+}
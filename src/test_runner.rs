@@ -0,0 +1,139 @@
+use std::{env, fs, path::{Path, PathBuf}, process::Command};
+
+/// What a test script expects, read from its comments:
+///
+/// - `// expect: <text>` for each line the script prints, in order;
+/// - `// expect runtime error: <message>` if it should stop with a runtime
+///   error (exit code 70);
+/// - `// expect error: <message>` for each scan, parse or resolve error it
+///   should be rejected with (exit code 65).
+#[derive(Default)]
+struct Expectations {
+    output: Vec<String>,
+    runtime_error: Option<String>,
+    errors: Vec<String>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Self {
+        let mut expectations = Expectations::default();
+        for line in source.lines() {
+            let Some((_, comment)) = line.split_once("// expect") else { continue };
+            if let Some(text) = comment.strip_prefix(": ") {
+                expectations.output.push(text.to_string());
+            } else if let Some(message) = comment.strip_prefix(" runtime error: ") {
+                expectations.runtime_error = Some(message.to_string());
+            } else if let Some(message) = comment.strip_prefix(" error: ") {
+                expectations.errors.push(message.to_string());
+            }
+        }
+        expectations
+    }
+
+    fn exit_code(&self) -> i32 {
+        if !self.errors.is_empty() {
+            65
+        } else if self.runtime_error.is_some() {
+            70
+        } else {
+            0
+        }
+    }
+}
+
+/// Runs every `.lox` file in `paths`, searching directories recursively,
+/// and prints which ones didn't behave as their comments say. Each test runs
+/// in its own rlox process with `flags` passed through. Returns whether all
+/// of them passed.
+pub fn run_tests(paths: &[String], flags: &[String]) -> bool {
+    let mut files = Vec::new();
+    for path in paths {
+        collect(Path::new(path), &mut files);
+    }
+
+    let program = match env::current_exe() {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("Error finding the rlox executable: {err}");
+            return false;
+        },
+    };
+
+    let mut failed = 0;
+    for file in &files {
+        let failures = run_test(&program, file, flags);
+        if failures.is_empty() {
+            println!("PASS {}", file.display());
+        } else {
+            failed += 1;
+            println!("FAIL {}", file.display());
+            for failure in failures {
+                println!("     {failure}");
+            }
+        }
+    }
+    println!("\n{} passed, {failed} failed.", files.len() - failed);
+    failed == 0
+}
+
+fn collect(path: &Path, files: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        return files.push(path.to_path_buf());
+    }
+    let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect(),
+        Err(err) => return eprintln!("Error reading {}: {err}", path.display()),
+    };
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|extension| extension == "lox") {
+            collect(&entry, files);
+        }
+    }
+}
+
+/// Runs one test and describes each way it went wrong.
+fn run_test(program: &Path, file: &Path, flags: &[String]) -> Vec<String> {
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(err) => return vec![format!("Error reading file: {err}")],
+    };
+    let expectations = Expectations::parse(&source);
+
+    let output = match Command::new(program).args(flags).arg("run").arg(file).output() {
+        Ok(output) => output,
+        Err(err) => return vec![format!("Error running rlox: {err}")],
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let mut failures = Vec::new();
+    let lines: Vec<&str> = stdout.lines().collect();
+    for (index, expected) in expectations.output.iter().enumerate() {
+        match lines.get(index) {
+            Some(line) if line == expected => (),
+            Some(line) => failures.push(format!("line {}: expected '{expected}', got '{line}'", index + 1)),
+            None => failures.push(format!("line {}: expected '{expected}', got nothing", index + 1)),
+        }
+    }
+    for line in lines.iter().skip(expectations.output.len()) {
+        failures.push(format!("unexpected output '{line}'"));
+    }
+
+    let messages = expectations.errors.iter().chain(&expectations.runtime_error);
+    for message in messages {
+        if !stderr.contains(message.as_str()) {
+            failures.push(format!("expected the error '{message}'"));
+        }
+    }
+    let expected_code = expectations.exit_code();
+    match output.status.code() {
+        Some(code) if code == expected_code => (),
+        Some(code) => failures.push(format!("expected exit code {expected_code}, got {code}")),
+        None => failures.push(String::from("rlox was killed by a signal")),
+    }
+    if !failures.is_empty() && !stderr.is_empty() {
+        failures.extend(stderr.lines().map(|line| format!("| {line}")));
+    }
+    failures
+}
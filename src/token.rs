@@ -1,15 +1,40 @@
 use crate::{token_type::{TokenType}, literal::Literal};
 
+/// A range of source text. `start` and `end` are byte offsets into the
+/// source, `line` and `column` are the 1-based position of `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Span { start, end, line, column }
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn merge(self, other: Span) -> Span {
+        let (first, last) = if self.start <= other.start { (self, other) } else { (other, self) };
+        Span { start: first.start, end: first.end.max(last.end), line: first.line, column: first.column }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token{
     pub token_type: TokenType,
     pub lexeme: String,
     pub literal: Option<Literal>,
-    pub line: usize
+    pub span: Span,
+    /// The `///` doc comment lines directly before this token. The parser
+    /// moves them onto the name of the declaration they document.
+    pub doc: Option<String>,
 }
 
 impl Token{
-    pub fn new(token_type: TokenType, lexeme: String, literal: Option<Literal>, line: usize) -> Token{
-        Self{token_type, lexeme, literal, line}
+    pub fn new(token_type: TokenType, lexeme: String, literal: Option<Literal>, span: Span) -> Token{
+        Self{token_type, lexeme, literal, span, doc: None}
     }
-}
\ No newline at end of file
+}
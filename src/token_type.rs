@@ -1,19 +1,23 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType{
-    LeftParen, RightParen, LeftBrace, RightBrace,
-    Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+    LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
+    Comma, Colon, Dot, Minus, Plus, Semicolon, Slash, Star, Percent,
     // One or two character tokens.
     Bang, BangEqual,
     Equal, EqualEqual,
     Greater, GreaterEqual,
     Less, LessEqual,
+    TildeSlash,
 
       // Literals.
     Identifier, String, Number,
+    // A string segment ending in `${`; the rest of the string follows the
+    // interpolated expression.
+    Interpolation,
 
     // Keywords.
-    And, Class, Else, False, Fun, For, If, Nil, Or,
+    And, Break, Class, Continue, Else, False, Fun, For, If, Nil, Or,
     Print, Return, Super, This, True, Var, While,
 
-    Eof
+    Error, Eof
 }
\ No newline at end of file
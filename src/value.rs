@@ -0,0 +1,242 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+
+use crate::{chunk::Chunk, literal::Literal, native::{LoxMap, MapKey, Native, NativeValue}, number::Number};
+
+/// A runtime value of the bytecode VM. Heap objects are reference counted so
+/// copying a value onto the stack never clones the object itself.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(Rc<str>),
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    Native(Rc<Native<Value>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<LoxMap<Value>>>),
+    Class(Rc<RefCell<Class>>),
+    Instance(Rc<RefCell<Instance>>),
+    BoundMethod(Rc<BoundMethod>),
+}
+
+#[derive(Debug, Default)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A captured variable. It points at a stack slot while the variable is still
+/// live and owns the value once the slot has been popped.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub methods: HashMap<Rc<str>, Rc<Closure>>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<RefCell<Class>>,
+    pub fields: HashMap<Rc<str>, Value>,
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+impl Value {
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Int(value) => Some(Number::Int(*value)),
+            Value::Float(value) => Some(Number::Float(*value)),
+            _ => None,
+        }
+    }
+
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn stringify(&self) -> String {
+        self.stringify_within(&mut Vec::new())
+    }
+
+    /// Like `Literal::stringify_within`: a list or map that contains itself
+    /// prints as `[...]` or `{...}` where it repeats.
+    fn stringify_within(&self, open: &mut Vec<*const ()>) -> String {
+        match self {
+            Value::Nil => String::from("nil"),
+            Value::Bool(value) => value.to_string(),
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) => Literal::format_number(*value),
+            Value::String(value) => value.to_string(),
+            Value::Function(_) | Value::Closure(_) | Value::Native(_) | Value::BoundMethod(_) => String::from("<fn>"),
+            Value::List(items) => {
+                let pointer = Rc::as_ptr(items) as *const ();
+                if open.contains(&pointer) {
+                    return String::from("[...]");
+                }
+                open.push(pointer);
+                let items: Vec<String> = items.borrow().iter().map(|item| item.stringify_within(open)).collect();
+                open.pop();
+                format!("[{}]", items.join(", "))
+            },
+            Value::Map(map) => {
+                let pointer = Rc::as_ptr(map) as *const ();
+                if open.contains(&pointer) {
+                    return String::from("{...}");
+                }
+                open.push(pointer);
+                let entries: Vec<String> = map.borrow().iter()
+                    .map(|(key, value)| format!("{}: {}", Value::from_key(key).stringify_within(open), value.stringify_within(open)))
+                    .collect();
+                open.pop();
+                format!("{{{}}}", entries.join(", "))
+            },
+            Value::Class(class) => class.borrow().name.clone(),
+            Value::Instance(instance) => format!("{} instance", instance.borrow().class.borrow().name),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::Closure(left), Value::Closure(right)) => Rc::ptr_eq(left, right),
+            (Value::Native(left), Value::Native(right)) => Rc::ptr_eq(left, right),
+            (Value::List(left), Value::List(right)) => Rc::ptr_eq(left, right),
+            (Value::Map(left), Value::Map(right)) => Rc::ptr_eq(left, right),
+            (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            (Value::BoundMethod(left), Value::BoundMethod(right)) => Rc::ptr_eq(left, right),
+            _ => match (self.as_number(), other.as_number()) {
+                (Some(left), Some(right)) => Number::equals(left, right),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Null => Value::Nil,
+            Literal::Bool(value) => Value::Bool(*value),
+            Literal::Int(value) => Value::Int(*value),
+            Literal::Float(value) => Value::Float(*value),
+            Literal::String(value) => Value::String(Rc::from(value.as_str())),
+            Literal::Callable(_) | Literal::Native(_) | Literal::List(_) | Literal::Map(_) | Literal::Class(_) | Literal::Instance(_) => {
+                unreachable!("the parser only produces primitive literals")
+            }
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Int(value) => Value::Int(value),
+            Number::Float(value) => Value::Float(value),
+        }
+    }
+}
+
+impl NativeValue for Value {
+    fn nil() -> Self {
+        Value::Nil
+    }
+
+    fn from_bool(value: bool) -> Self {
+        Value::Bool(value)
+    }
+
+    fn from_int(value: i64) -> Self {
+        Value::Int(value)
+    }
+
+    fn from_float(value: f64) -> Self {
+        Value::Float(value)
+    }
+
+    fn from_string(value: &str) -> Self {
+        Value::String(Rc::from(value))
+    }
+
+    fn from_list(items: Vec<Self>) -> Self {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    fn from_map(map: LoxMap<Self>) -> Self {
+        Value::Map(Rc::new(RefCell::new(map)))
+    }
+
+    fn from_key(key: &MapKey) -> Self {
+        match key {
+            MapKey::Nil => Value::Nil,
+            MapKey::Bool(value) => Value::Bool(*value),
+            MapKey::Int(value) => Value::Int(*value),
+            MapKey::Float(bits) => Value::Float(f64::from_bits(*bits)),
+            MapKey::String(value) => Value::String(Rc::from(value.as_str())),
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn as_list(&self) -> Option<&Rc<RefCell<Vec<Self>>>> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    fn as_map(&self) -> Option<&Rc<RefCell<LoxMap<Self>>>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    fn to_key(&self) -> Option<MapKey> {
+        match self {
+            Value::Nil => Some(MapKey::Nil),
+            Value::Bool(value) => Some(MapKey::Bool(*value)),
+            Value::Int(value) => Some(MapKey::Int(*value)),
+            Value::Float(value) => Some(MapKey::from_float(*value)),
+            Value::String(value) => Some(MapKey::String(value.to_string())),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, rc::Rc};
+
+use crate::{
+    chunk::OpCode,
+    error_handler::LoxError,
+    number::{ArithmeticOp, Number},
+    token::Token,
+    token_type::TokenType,
+    native,
+    value::{BoundMethod, Class, Closure, Function, Instance, Upvalue, Value},
+};
+
+const FRAMES_MAX: usize = 256;
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    /// Index of the frame's first stack slot, which holds the callee.
+    slots: usize,
+}
+
+/// A stack-based virtual machine running the bytecode produced by `Compiler`.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    /// Upvalues still pointing into the stack, ordered by slot.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut vm = Vm { stack: Vec::new(), frames: Vec::new(), globals: HashMap::new(), open_upvalues: Vec::new() };
+        for native in native::natives() {
+            vm.globals.insert(Rc::from(native.name), Value::Native(Rc::new(native)));
+        }
+        vm
+    }
+
+    pub fn global_names(&self) -> Vec<String> {
+        self.globals.keys().map(|name| name.to_string()).collect()
+    }
+
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.globals.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+    }
+
+    /// Runs `function` as a script and returns the value it returns, which
+    /// is nil unless it was compiled with `Compiler::compile_expression`.
+    pub fn interpret(&mut self, function: Rc<Function>) -> Result<Value, LoxError> {
+        let closure = Rc::new(Closure { function, upvalues: Vec::new() });
+        self.stack.push(Value::Closure(Rc::clone(&closure)));
+
+        let result = self.call(closure, 0).and_then(|_| self.run());
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<Value, LoxError> {
+        loop {
+            let byte = self.read_byte();
+            let op = OpCode::from_byte(byte).unwrap_or_else(|| panic!("invalid opcode {byte}"));
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                },
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Bool(true)),
+                OpCode::False => self.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                },
+                OpCode::GetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.push(self.stack[slot].clone());
+                },
+                OpCode::SetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                },
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(&name) {
+                        Some(value) => self.push(value.clone()),
+                        None => return Err(self.undefined_variable(&name)),
+                    }
+                },
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                },
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(global) => *global = value,
+                        None => return Err(self.undefined_variable(&name)),
+                    }
+                },
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push(value);
+                },
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                },
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let Value::Instance(instance) = self.peek(0).clone() else {
+                        return Err(self.error("Only instances have properties."));
+                    };
+                    let field = instance.borrow().fields.get(&name).cloned();
+                    match field {
+                        Some(value) => {
+                            self.pop();
+                            self.push(value);
+                        },
+                        None => {
+                            let class = Rc::clone(&instance.borrow().class);
+                            self.bind_method(&class, &name)?;
+                        },
+                    }
+                },
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let Value::Instance(instance) = self.peek(1).clone() else {
+                        return Err(self.error("Only instances have fields."));
+                    };
+                    let value = self.pop();
+                    instance.borrow_mut().fields.insert(name, value.clone());
+                    self.pop();
+                    self.push(value);
+                },
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.error("Superclass must be a class."));
+                    };
+                    self.bind_method(&superclass, &name)?;
+                },
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(Value::Bool(left == right));
+                },
+                OpCode::Greater => self.comparison(|ordering| ordering == Some(Ordering::Greater))?,
+                OpCode::GreaterEqual => self.comparison(|ordering| matches!(ordering, Some(Ordering::Greater | Ordering::Equal)))?,
+                OpCode::Less => self.comparison(|ordering| ordering == Some(Ordering::Less))?,
+                OpCode::LessEqual => self.comparison(|ordering| matches!(ordering, Some(Ordering::Less | Ordering::Equal)))?,
+                OpCode::Add => {
+                    if let (Value::String(left), Value::String(right)) = (self.peek(1), self.peek(0)) {
+                        let value = Value::String(Rc::from(format!("{left}{right}")));
+                        self.pop();
+                        self.pop();
+                        self.push(value);
+                    } else {
+                        self.arithmetic(ArithmeticOp::Add)?;
+                    }
+                },
+                OpCode::Subtract => self.arithmetic(ArithmeticOp::Subtract)?,
+                OpCode::Multiply => self.arithmetic(ArithmeticOp::Multiply)?,
+                OpCode::Divide => self.arithmetic(ArithmeticOp::Divide)?,
+                OpCode::FloorDivide => self.arithmetic(ArithmeticOp::FloorDivide)?,
+                OpCode::Modulo => self.arithmetic(ArithmeticOp::Modulo)?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Bool(value.is_falsey()));
+                },
+                OpCode::Negate => {
+                    let Some(number) = self.peek(0).as_number() else {
+                        return Err(self.error("Operand must be a number"));
+                    };
+                    let value = number.negate().map_err(|message| self.error(message))?;
+                    self.pop();
+                    self.push(Value::from(value));
+                },
+                OpCode::Print => println!("{}", self.pop().stringify()),
+                OpCode::Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip += offset;
+                },
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if self.peek(0).is_falsey() {
+                        self.frame_mut().ip += offset;
+                    }
+                },
+                OpCode::Loop => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip -= offset;
+                },
+                OpCode::Call => {
+                    let argument_count = self.read_byte() as usize;
+                    self.call_value(self.peek(argument_count).clone(), argument_count)?;
+                },
+                OpCode::Invoke => {
+                    let name = self.read_string();
+                    let argument_count = self.read_byte() as usize;
+                    self.invoke(&name, argument_count)?;
+                },
+                OpCode::SuperInvoke => {
+                    let name = self.read_string();
+                    let argument_count = self.read_byte() as usize;
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.error("Superclass must be a class."));
+                    };
+                    self.invoke_from_class(&superclass, &name, argument_count)?;
+                },
+                OpCode::Closure => {
+                    let Value::Function(function) = self.read_constant() else {
+                        unreachable!("closures are always made from function constants")
+                    };
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        if is_local {
+                            let slot = self.frame().slots + index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        } else {
+                            upvalues.push(Rc::clone(&self.frame().closure.upvalues[index]));
+                        }
+                    }
+                    self.push(Value::Closure(Rc::new(Closure { function, upvalues })));
+                },
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                },
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("a function is running");
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.push(result);
+                },
+                OpCode::BuildList => {
+                    let count = self.read_u16() as usize;
+                    let items = self.stack.split_off(self.stack.len() - count);
+                    self.push(Value::List(Rc::new(RefCell::new(items))));
+                },
+                OpCode::BuildMap => {
+                    let count = self.read_u16() as usize;
+                    let mut entries = self.stack.split_off(self.stack.len() - 2 * count).into_iter();
+                    let mut pairs = Vec::with_capacity(count);
+                    while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+                        pairs.push((key, value));
+                    }
+                    let map = native::map_from_pairs(pairs).map_err(|message| self.error(&message))?;
+                    self.push(map);
+                },
+                OpCode::Stringify => {
+                    let value = self.pop();
+                    self.push(Value::String(Rc::from(value.stringify())));
+                },
+                OpCode::GetIndex => {
+                    let value = native::index_get(self.peek(1), self.peek(0)).map_err(|message| self.error(&message))?;
+                    self.pop();
+                    self.pop();
+                    self.push(value);
+                },
+                OpCode::SetIndex => {
+                    let value = self.peek(0).clone();
+                    native::index_set(self.peek(2), self.peek(1), value.clone()).map_err(|message| self.error(&message))?;
+                    self.stack.truncate(self.stack.len() - 3);
+                    self.push(value);
+                },
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = Class { name: name.to_string(), methods: HashMap::new() };
+                    self.push(Value::Class(Rc::new(RefCell::new(class))));
+                },
+                OpCode::Inherit => {
+                    let Value::Class(superclass) = self.peek(1).clone() else {
+                        return Err(self.error("Superclass must be a class."));
+                    };
+                    let Value::Class(subclass) = self.peek(0).clone() else {
+                        return Err(self.error("Only classes can inherit."));
+                    };
+                    let methods = superclass.borrow().methods.clone();
+                    subclass.borrow_mut().methods.extend(methods);
+                    self.pop();
+                },
+                OpCode::Method => {
+                    let name = self.read_string();
+                    let (Value::Closure(method), Value::Class(class)) = (self.peek(0).clone(), self.peek(1).clone()) else {
+                        return Err(self.error("Methods must be functions defined on a class."));
+                    };
+                    class.borrow_mut().methods.insert(name, method);
+                    self.pop();
+                },
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, argument_count: usize) -> Result<(), LoxError> {
+        let callee_slot = self.stack.len() - argument_count - 1;
+        match callee {
+            Value::Closure(closure) => self.call(closure, argument_count),
+            Value::Native(native) => {
+                self.check_arity(native.arity, argument_count)?;
+                let result = (native.function)(&self.stack[callee_slot + 1..]).map_err(|message| self.error(&message))?;
+                self.stack.truncate(callee_slot);
+                self.push(result);
+                Ok(())
+            },
+            Value::Class(class) => {
+                let instance = Instance { class: Rc::clone(&class), fields: HashMap::new() };
+                self.stack[callee_slot] = Value::Instance(Rc::new(RefCell::new(instance)));
+                let initializer = class.borrow().methods.get("init").cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, argument_count),
+                    None => self.check_arity(0, argument_count),
+                }
+            },
+            Value::BoundMethod(bound) => {
+                self.stack[callee_slot] = bound.receiver.clone();
+                self.call(Rc::clone(&bound.method), argument_count)
+            },
+            _ => Err(self.error("Can only call functions and classes.")),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, argument_count: usize) -> Result<(), LoxError> {
+        self.check_arity(closure.function.arity, argument_count)?;
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error("Stack overflow."));
+        }
+        let slots = self.stack.len() - argument_count - 1;
+        self.frames.push(CallFrame { closure, ip: 0, slots });
+        Ok(())
+    }
+
+    fn check_arity(&self, expected: usize, got: usize) -> Result<(), LoxError> {
+        if expected != got {
+            return Err(self.error(&format!("Expected {} arguments, but got {}.", expected, got)));
+        }
+        Ok(())
+    }
+
+    fn invoke(&mut self, name: &Rc<str>, argument_count: usize) -> Result<(), LoxError> {
+        let Value::Instance(instance) = self.peek(argument_count).clone() else {
+            return Err(self.error("Only instances have properties."));
+        };
+        let field = instance.borrow().fields.get(name).cloned();
+        if let Some(field) = field {
+            let callee_slot = self.stack.len() - argument_count - 1;
+            self.stack[callee_slot] = field.clone();
+            return self.call_value(field, argument_count);
+        }
+        let class = Rc::clone(&instance.borrow().class);
+        self.invoke_from_class(&class, name, argument_count)
+    }
+
+    fn invoke_from_class(&mut self, class: &Rc<RefCell<Class>>, name: &Rc<str>, argument_count: usize) -> Result<(), LoxError> {
+        let method = class.borrow().methods.get(name).cloned();
+        match method {
+            Some(method) => self.call(method, argument_count),
+            None => Err(self.error(&format!("Undefined property '{name}'."))),
+        }
+    }
+
+    /// Replaces the receiver on top of the stack with its method `name`.
+    fn bind_method(&mut self, class: &Rc<RefCell<Class>>, name: &Rc<str>) -> Result<(), LoxError> {
+        let method = class.borrow().methods.get(name).cloned();
+        let Some(method) = method else {
+            return Err(self.error(&format!("Undefined property '{name}'.")));
+        };
+        let receiver = self.pop();
+        self.push(Value::BoundMethod(Rc::new(BoundMethod { receiver, method })));
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let position = self.open_upvalues.partition_point(|upvalue| Vm::open_slot(upvalue) < slot);
+        if let Some(existing) = self.open_upvalues.get(position) {
+            if Vm::open_slot(existing) == slot {
+                return Rc::clone(existing);
+            }
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.insert(position, Rc::clone(&upvalue));
+        upvalue
+    }
+
+    /// Moves every captured variable living at or above `last` off the stack.
+    fn close_upvalues(&mut self, last: usize) {
+        let position = self.open_upvalues.partition_point(|upvalue| Vm::open_slot(upvalue) < last);
+        for upvalue in self.open_upvalues.drain(position..) {
+            let slot = Vm::open_slot(&upvalue);
+            *upvalue.borrow_mut() = Upvalue::Closed(self.stack[slot].clone());
+        }
+    }
+
+    fn open_slot(upvalue: &Rc<RefCell<Upvalue>>) -> usize {
+        match *upvalue.borrow() {
+            Upvalue::Open(slot) => slot,
+            Upvalue::Closed(_) => unreachable!("closed upvalues are removed from the open list"),
+        }
+    }
+
+    fn arithmetic(&mut self, op: ArithmeticOp) -> Result<(), LoxError> {
+        let (Some(left), Some(right)) = (self.peek(1).as_number(), self.peek(0).as_number()) else {
+            return Err(self.error("Operands must be two numbers or two strings"));
+        };
+        let value = Number::arithmetic(op, left, right).map_err(|message| self.error(message))?;
+        self.pop();
+        self.pop();
+        self.push(Value::from(value));
+        Ok(())
+    }
+
+    fn comparison(&mut self, test: fn(Option<Ordering>) -> bool) -> Result<(), LoxError> {
+        let (Some(left), Some(right)) = (self.peek(1).as_number(), self.peek(0).as_number()) else {
+            return Err(self.error("Operands must be two numbers or two strings"));
+        };
+        let value = test(Number::compare(left, right));
+        self.pop();
+        self.pop();
+        self.push(Value::Bool(value));
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let value = frame.closure.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_u16() as usize;
+        self.frame().closure.function.chunk.constants[index].clone()
+    }
+
+    fn read_string(&mut self) -> Rc<str> {
+        match self.read_constant() {
+            Value::String(name) => name,
+            constant => unreachable!("expected a name constant, found {constant:?}"),
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("a function is running")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("a function is running")
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the stack is not empty")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn undefined_variable(&self, name: &str) -> LoxError {
+        self.error(&format!("Undefined variable '{name}'."))
+            .with_help(&format!("declare it with 'var {name} = ...;' before using it"))
+    }
+
+    /// A runtime error located at the instruction currently being executed.
+    /// Operands share their opcode's span, so the last byte read is enough.
+    fn error(&self, message: &str) -> LoxError {
+        let frame = self.frame();
+        let span = frame.closure.function.chunk.span_at(frame.ip.saturating_sub(1));
+        LoxError::runtime(&Token::new(TokenType::Error, String::new(), None, span), message)
+    }
+}
@@ -0,0 +1,22 @@
+//! Runs the `.lox` scripts in `test/` with `rlox test` on each backend.
+
+use std::process::Command;
+
+fn run_corpus(backend: &str) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["--backend", backend, "test", "test"])
+        .output()
+        .expect("rlox runs");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn tree_walker() {
+    run_corpus("tree");
+}
+
+#[test]
+fn bytecode_vm() {
+    run_corpus("vm");
+}